@@ -0,0 +1,117 @@
+//! Hash OpenCC dicts for versioning the browser cache, and compress compiled
+//! dicts for embedding into the worker (feature `embed-dict`)
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const DICT_DIR: &str = "public/dict";
+/// Dict compiler shared with `scripts/compile_dict` & the worker tests
+const DICT_COMPILER: &str = "src/worker/dict_compile.rs";
+
+/// Dicts referred by `ChineseConvertion::dict_chain()`, keep them in sync
+const REQUIRED_DICTS: &[&str] = &[
+    "s2t", "t2s", "t2tw", "t2twp", "t2hk", "tw2t", "hk2t", "jp2t", "t2jp",
+];
+
+fn main() {
+    check_dicts();
+    hash_dicts();
+    #[cfg(feature = "embed-dict")]
+    embed_dicts();
+}
+
+/// Sorted paths of dict sources (`*.txt`)
+fn dict_sources() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(DICT_DIR)
+        .expect("failed to read dict dir")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Fail on missing or empty dicts, convertions using them would fail at
+/// runtime (e.g. jp2t/t2jp are only fetched by `scripts/update_dict.py`)
+fn check_dicts() {
+    let missing: Vec<_> = REQUIRED_DICTS
+        .iter()
+        .filter(|name| {
+            let path = Path::new(DICT_DIR).join(format!("{name}.txt"));
+            !fs::metadata(path).is_ok_and(|meta| meta.len() > 0)
+        })
+        .collect();
+    if !missing.is_empty() {
+        panic!(
+            "dicts {missing:?} missing or empty in {DICT_DIR}, \
+            run scripts/update_dict.py to fetch them"
+        );
+    }
+}
+
+/// Set `DICT_HASH` to FNV-1a hash of the dict sources & the compiler, so
+/// the browser cache is invalidated whenever the compiled dicts change
+fn hash_dicts() {
+    println!("cargo::rerun-if-changed={DICT_DIR}");
+    println!("cargo::rerun-if-changed={DICT_COMPILER}");
+
+    let mut paths = dict_sources();
+    paths.push(PathBuf::from(DICT_COMPILER));
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for path in paths {
+        let name = path.file_name().unwrap().as_encoded_bytes();
+        let bytes = fs::read(&path).expect("failed to read dict");
+        for b in name.iter().chain(&bytes) {
+            hash = (hash ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+    println!("cargo::rustc-env=DICT_HASH={hash:016x}");
+}
+
+// compile dict sources by the same code as the `.bin` files fetched by the
+// worker, so embedding doesn't depend on trunk's hook
+#[cfg(feature = "embed-dict")]
+include!("src/worker/dict_compile.rs");
+
+#[cfg(feature = "embed-dict")]
+fn embed_dicts() {
+    use std::{env, fmt::Write, path::PathBuf};
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let paths = dict_sources();
+    if paths.is_empty() {
+        panic!("no dict found in {DICT_DIR}, run scripts/update_dict.py first");
+    }
+
+    let mut arms = String::new();
+    for path in paths {
+        let name = path.with_extension("bin");
+        let name = name.file_name().unwrap().to_str().unwrap();
+        let text = fs::read_to_string(&path).expect("failed to read dict");
+        let output = out_dir.join(format!("{name}.deflate"));
+        fs::write(
+            &output,
+            miniz_oxide::deflate::compress_to_vec(&compile_dict(&text), 10),
+        )
+        .expect("failed to write compressed dict");
+        writeln!(
+            arms,
+            "        {:?} => Some(include_bytes!({:?}).as_slice()),",
+            format!("dict/{name}"),
+            output
+        )
+        .unwrap();
+    }
+    let code = format!(
+        "pub(crate) fn embedded_dict(name: &str) -> Option<&'static [u8]> {{
+    match name {{
+{arms}        _ => None,
+    }}
+}}
+"
+    );
+    fs::write(out_dir.join("embedded_dict.rs"), code).expect("failed to write embedded_dict.rs");
+}
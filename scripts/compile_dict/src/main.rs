@@ -0,0 +1,37 @@
+//! Compile OpenCC dicts (public/dict/*.txt) into binary form (*.bin) for the
+//! worker to fetch, run from the repo root by trunk's pre_build hook.
+//!
+//! The compiler itself is shared with `build.rs` & the worker tests.
+
+use std::{fs, path::Path};
+
+include!("../../../src/worker/dict_compile.rs");
+
+const DICT_DIR: &str = "public/dict";
+
+/// True if `output` is newer than `source`
+fn is_up_to_date(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    match (modified(source), modified(output)) {
+        (Ok(source), Ok(output)) => output >= source,
+        _ => false,
+    }
+}
+
+fn main() {
+    let mut paths: Vec<_> = fs::read_dir(DICT_DIR)
+        .expect("failed to read dict dir")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let output = path.with_extension("bin");
+        if is_up_to_date(&path, &output) {
+            continue;
+        }
+        println!("Compile {}", path.display());
+        let text = fs::read_to_string(&path).expect("failed to read dict");
+        fs::write(&output, compile_dict(&text)).expect("failed to write compiled dict");
+    }
+}
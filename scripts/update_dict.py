@@ -1,29 +1,58 @@
 #!/usr/bin/env python3
-"""Update OpenCC directories (public/{s2t,t2s}.txt)"""
+# /// script
+# dependencies = ["requests"]
+# ///
+"""Update OpenCC directories (public/dict/*.txt)"""
 
 from pathlib import Path
 import requests
 
 DICT_URL = "https://raw.githubusercontent.com/BYVoid/OpenCC/refs/heads/master/data/dictionary/"
-OUTPUT_DIR = "public/"
+OUTPUT_DIR = "public/dict/"
 
+# Dicts end with "Rev" are not in the repo, they are reversed from the
+# original one, same as what OpenCC does on its building.
 DICTS = {
     "s2t.txt": ["STCharacters.txt", "STPhrases.txt"],
     "t2s.txt": ["TSCharacters.txt", "TSPhrases.txt"],
+    "t2tw.txt": ["TWVariants.txt"],
+    "t2twp.txt": ["TWPhrases.txt"],
+    "t2hk.txt": ["HKVariants.txt"],
+    "tw2t.txt": ["TWVariantsRevPhrases.txt", "TWVariantsRev"],
+    "hk2t.txt": ["HKVariantsRevPhrases.txt", "HKVariantsRev"],
+    "jp2t.txt": ["JPShinjitaiPhrases.txt", "JPShinjitaiCharacters.txt", "JPVariantsRev"],
+    "t2jp.txt": ["JPVariants.txt"],
 }
 
 
+def _fetch_items(dict_file):
+    if dict_file.endswith("Rev"):
+        for preimage, images in _fetch_items(f"{dict_file[:-3]}.txt"):
+            for image in images:
+                yield image, [preimage]
+        return
+    url = f"{DICT_URL}{dict_file}"
+    print('Fetch', url)
+    with requests.get(url, stream=True) as resp:
+        resp.raise_for_status()
+        for line in resp.iter_lines():
+            line = line.decode('utf-8').strip()
+            if not line or line.startswith('#'):
+                continue
+            preimage, images = line.split('\t')
+            yield preimage, images.split()
+
+
 def main():
     for output_name, dict_files in DICTS.items():
         output = Path(OUTPUT_DIR) / Path(output_name)
-        with output.open('w', encoding='utf-8') as out:
+        # write aside, a failed fetch must not leave a truncated dict behind
+        partial = output.with_suffix('.part')
+        with partial.open('w', encoding='utf-8') as out:
             for dict_file in dict_files:
-                url = f"{DICT_URL}{dict_file}"
-                print('Fetch', url)
-                with requests.get(url, stream=True) as resp:
-                    for line in resp.iter_lines():
-                        preimage, images = line.decode('utf-8').strip().split('\t')
-                        out.write(f"{preimage}\t{images.split()[0]}\n")
+                for preimage, images in _fetch_items(dict_file):
+                    out.write(f"{preimage}\t{images[0]}\n")
+        partial.replace(output)
         print('Dict updated', output)
 
 
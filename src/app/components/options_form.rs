@@ -1,11 +1,20 @@
-use leptos::prelude::*;
+use leptos::{prelude::*, task::spawn_local};
 use leptos_i18n::{t, t_string};
 use reactive_stores::Store;
+use wasm_bindgen_futures::JsFuture;
 
-use crate::{ChineseConvertion, LineStrip, Options, OptionsStoreFields, app::i18n::use_i18n};
+use crate::{
+    ChineseConvertion, DropField, DropRule, LineEnding, LineStrip, NormalForm, OptionRule, Options,
+    OptionsStoreFields, ReplaceRule, ReplaceStage, SdhRemovalStoreFields,
+    TextNormalizationStoreFields, UserDict, UserDictStoreFields,
+    app::{
+        converter::{Converter, MAX_WORKERS},
+        i18n::use_i18n,
+    },
+};
 
 #[component]
-pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
+pub(crate) fn OptionsForm(options: Store<Options>, user_dict: Store<UserDict>) -> impl IntoView {
     let i18n = use_i18n();
     let offset_string = RwSignal::new({
         let ms: i32 = options.offset_millis().get_untracked();
@@ -18,12 +27,18 @@ pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
     // workaround: <select> won't select prop:value on the first render
     Effect::new(move || {
         options.chinese_convertion().notify();
+        options.line_ending().notify();
         options.line_strip().notify();
+        options.replace_stage().notify();
+        options.normalization().form().notify();
+        options.drop_rules().notify();
+        options.option_rules().notify();
     });
 
     view! {
         <datalist id="charsets">
             <option label=move || t_string!(i18n, opt_charset_utf8) value="UTF-8" />
+            <option label=move || t_string!(i18n, opt_charset_utf16) value="UTF-16LE" />
             <option label=move || t_string!(i18n, opt_charset_gb) value="GB18030" />
             <option label=move || t_string!(i18n, opt_charset_big5) value="Big5" />
             <option label=move || t_string!(i18n, opt_charset_jis) value="Shift_JIS" />
@@ -37,15 +52,91 @@ pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
             bind:value=options.ass_charset()
         />
 
+        <label for="fallback-charsets">{t!(i18n, opt_fallback_encodings_label)}</label>
+        <input
+            type="text"
+            id="fallback-charsets"
+            placeholder=move || t_string!(i18n, opt_fallback_encodings_placeholder)
+            title=move || t_string!(i18n, opt_fallback_encodings_title)
+            bind:value=options.fallback_charsets()
+        />
+
+        <label class="checkbox" title=move || t_string!(i18n, opt_mojibake_repair_title)>
+            <input type="checkbox" id="mojibake-repair" bind:checked=options.mojibake_repair() />
+            {t!(i18n, opt_mojibake_repair_label)}
+        </label>
+
         <label for="out-charset">{t!(i18n, opt_srt_encoding_label)}</label>
         <input
             type="text"
             id="out-charset"
             list="charsets"
             placeholder=move || t_string!(i18n, opt_srt_encoding_placeholder)
+            disabled=move || options.same_charset().get()
             bind:value=options.srt_charset()
         />
 
+        <label class="checkbox">
+            <input type="checkbox" id="same-charset" bind:checked=options.same_charset() />
+            {t!(i18n, opt_same_charset_label)}
+        </label>
+
+        <label for="line-ending">{t!(i18n, opt_line_ending_label)}</label>
+        <select
+            id="line-ending"
+            prop:value=move || {
+                let value: &str = options.line_ending().get().into();
+                value
+            }
+            on:change:target=move |ev| {
+                let value = ev.target().value().parse().unwrap();
+                options.line_ending().set(value);
+            }
+        >
+            <option value=LineEnding::Crlf>{t!(i18n, opt_line_ending_crlf)}</option>
+            <option value=LineEnding::Lf>{t!(i18n, opt_line_ending_lf)}</option>
+        </select>
+
+        <label class="checkbox">
+            <input type="checkbox" id="utf8-bom" bind:checked=options.utf8_bom() />
+            {t!(i18n, opt_utf8_bom_label)}
+        </label>
+
+        <label for="normal-form">{t!(i18n, opt_normalize_label)}</label>
+        <select
+            id="normal-form"
+            prop:value=move || {
+                let value: &str = options.normalization().form().get().into();
+                value
+            }
+            on:change:target=move |ev| {
+                let value = ev.target().value().parse().unwrap();
+                options.normalization().form().set(value);
+            }
+        >
+            <option value=NormalForm::Keep>{t!(i18n, opt_normalize_no)}</option>
+            <option value=NormalForm::Nfc>"NFC"</option>
+            <option value=NormalForm::Nfkc>"NFKC"</option>
+        </select>
+
+        <fieldset class="checkboxes">
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.normalization().fullwidth_alnum() />
+                {t!(i18n, opt_normalize_fullwidth_alnum)}
+            </label>
+            <label class="checkbox">
+                <input
+                    type="checkbox"
+                    bind:checked=options.normalization().halfwidth_katakana()
+                />
+                {t!(i18n, opt_normalize_halfwidth_katakana)}
+            </label>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.normalization().zero_width() />
+                {t!(i18n, opt_normalize_zero_width)}
+            </label>
+        </fieldset>
+
         <label for="conv-dict">{t!(i18n, opt_chinese_convert_label)}</label>
         <select
             id="conv-dict"
@@ -58,15 +149,59 @@ pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
                 options.chinese_convertion().set(value);
             }
         >
-            <option value=ChineseConvertion::Keep>{t!(i18n, opt_chinese_convert_no)}</option>
-            <option value=ChineseConvertion::ToSimplified>
-                {t!(i18n, opt_chinese_convert_t2s)}
-            </option>
-            <option value=ChineseConvertion::ToTraditional>
-                {t!(i18n, opt_chinese_convert_st2)}
-            </option>
+            <ChineseConvertionOptions />
         </select>
 
+        <label class="checkbox">
+            <input type="checkbox" id="filename-tags" bind:checked=options.filename_tags() />
+            {t!(i18n, opt_filename_tags_label)}
+        </label>
+
+        <label for="user-dict">{t!(i18n, opt_user_dict_label)}</label>
+        <div class="user-dict">
+            <textarea
+                id="user-dict"
+                rows="3"
+                placeholder=move || t_string!(i18n, opt_user_dict_placeholder)
+                bind:value=user_dict.entries()
+            />
+            <input
+                type="file"
+                accept=".txt"
+                title=move || t_string!(i18n, opt_user_dict_upload)
+                on:change:target=move |ev| {
+                    let Some(file) = ev.target().files().and_then(|files| files.get(0)) else {
+                        return;
+                    };
+                    ev.target().set_value("");
+                    spawn_local(async move {
+                        match JsFuture::from(file.text()).await {
+                            Ok(text) => {
+                                let text = text.as_string().unwrap_or_default();
+                                user_dict
+                                    .entries()
+                                    .update(|entries| {
+                                        if !entries.is_empty() && !entries.ends_with('\n') {
+                                            entries.push('\n');
+                                        }
+                                        entries.push_str(&text);
+                                    });
+                            }
+                            Err(err) => log::warn!("failed to read user dict: {:?}", err),
+                        }
+                    });
+                }
+            />
+        </div>
+
+        <label for="glossary">{t!(i18n, opt_glossary_label)}</label>
+        <textarea
+            id="glossary"
+            rows="3"
+            placeholder=move || t_string!(i18n, opt_glossary_placeholder)
+            bind:value=user_dict.glossary()
+        />
+
         <label for="lines">{t!(i18n, opt_lines_label)}</label>
         <select
             id="lines"
@@ -84,6 +219,76 @@ pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
             <option value=LineStrip::KeepLast>{t!(i18n, opt_lines_last)}</option>
         </select>
 
+        <label>{t!(i18n, opt_replace_label)}</label>
+        <div class="rules">
+            <For
+                each=move || 0..options.replace_rules().read().len()
+                key=|idx| *idx
+                children=move |idx| view! { <ReplaceRuleInput options idx /> }
+            />
+            <button
+                type="button"
+                on:click=move |_| options.replace_rules().write().push(ReplaceRule::default())
+            >
+                {t!(i18n, opt_replace_add)}
+            </button>
+        </div>
+
+        <label for="replace-stage">{t!(i18n, opt_replace_stage_label)}</label>
+        <select
+            id="replace-stage"
+            prop:value=move || {
+                let value: &str = options.replace_stage().get().into();
+                value
+            }
+            on:change:target=move |ev| {
+                let value = ev.target().value().parse().unwrap();
+                options.replace_stage().set(value);
+            }
+        >
+            <option value=ReplaceStage::BeforeConvertion>
+                {t!(i18n, opt_replace_stage_before)}
+            </option>
+            <option value=ReplaceStage::AfterConvertion>
+                {t!(i18n, opt_replace_stage_after)}
+            </option>
+        </select>
+
+        <label>{t!(i18n, opt_rules_label)}</label>
+        <div class="rules">
+            <For
+                each=move || 0..options.option_rules().read().len()
+                key=|idx| *idx
+                children=move |idx| view! { <OptionRuleInput options idx /> }
+            />
+            <button
+                type="button"
+                on:click=move |_| options.option_rules().write().push(OptionRule::default())
+            >
+                {t!(i18n, opt_rules_add)}
+            </button>
+        </div>
+
+        <label>{t!(i18n, opt_drop_label)}</label>
+        <div class="rules">
+            <For
+                each=move || 0..options.drop_rules().read().len()
+                key=|idx| *idx
+                children=move |idx| view! { <DropRuleInput options idx /> }
+            />
+            <button
+                type="button"
+                on:click=move |_| options.drop_rules().write().push(DropRule::default())
+            >
+                {t!(i18n, opt_drop_add)}
+            </button>
+        </div>
+
+        <label class="checkbox">
+            <input type="checkbox" id="drop-credits" bind:checked=options.drop_credits() />
+            {t!(i18n, opt_drop_credits_label)}
+        </label>
+
         <label for="offset">{t!(i18n, opt_offset_label)}</label>
         <input
             type="text"
@@ -97,9 +302,310 @@ pub(crate) fn OptionsForm(options: Store<Options>) -> impl IntoView {
             }
         />
 
+        <label for="max-line-chars">{t!(i18n, opt_max_line_chars_label)}</label>
+        <input
+            type="number"
+            id="max-line-chars"
+            min="0"
+            placeholder=move || t_string!(i18n, opt_unlimited_placeholder)
+            prop:value=move || display_limit(options.max_line_chars().get())
+            on:change:target=move |ev| {
+                options.max_line_chars().set(ev.target().value().parse().unwrap_or_default());
+            }
+        />
+
+        <label for="max-lines">{t!(i18n, opt_max_lines_label)}</label>
+        <input
+            type="number"
+            id="max-lines"
+            min="0"
+            placeholder=move || t_string!(i18n, opt_unlimited_placeholder)
+            prop:value=move || display_limit(options.max_lines().get())
+            on:change:target=move |ev| {
+                options.max_lines().set(ev.target().value().parse().unwrap_or_default());
+            }
+        />
+
+        <fieldset class="checkboxes">
+            <legend>{t!(i18n, opt_sdh_label)}</legend>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.sdh_removal().brackets() />
+                {t!(i18n, opt_sdh_brackets)}
+            </label>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.sdh_removal().parentheses() />
+                {t!(i18n, opt_sdh_parentheses)}
+            </label>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.sdh_removal().music() />
+                {t!(i18n, opt_sdh_music)}
+            </label>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.sdh_removal().speaker_labels() />
+                {t!(i18n, opt_sdh_speaker_labels)}
+            </label>
+            <label class="checkbox">
+                <input type="checkbox" bind:checked=options.sdh_removal().all_caps() />
+                {t!(i18n, opt_sdh_all_caps)}
+            </label>
+        </fieldset>
+
         <label class="checkbox">
             <input type="checkbox" id="no-zip" bind:checked=options.no_zip() />
             {t!(i18n, opt_no_zip_label)}
         </label>
+
+        <label class="checkbox">
+            <input
+                type="checkbox"
+                id="zip-errors-file"
+                disabled=move || options.no_zip().get()
+                bind:checked=options.zip_errors_file()
+            />
+            {t!(i18n, opt_zip_errors_file_label)}
+        </label>
+
+        <label for="max-workers">{t!(i18n, opt_max_workers_label)}</label>
+        <input
+            type="number"
+            id="max-workers"
+            min="0"
+            max=MAX_WORKERS.to_string()
+            placeholder=move || {
+                let n = Converter::pool_size(0);
+                t_string!(i18n, opt_max_workers_placeholder, n)
+            }
+            prop:value=move || display_limit(options.max_workers().get())
+            on:change:target=move |ev| {
+                options.max_workers().set(ev.target().value().parse().unwrap_or_default());
+            }
+        />
     }
 }
+
+#[component]
+fn ReplaceRuleInput(options: Store<Options>, idx: usize) -> impl IntoView {
+    let i18n = use_i18n();
+    let rule = move || {
+        options
+            .replace_rules()
+            .read()
+            .get(idx)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let error = move || rule().compile().err().map(|err| err.to_string());
+    let update = move |f: &dyn Fn(&mut ReplaceRule)| {
+        if let Some(rule) = options.replace_rules().write().get_mut(idx) {
+            f(rule);
+        }
+    };
+
+    view! {
+        <div class="rule">
+            <input
+                type="text"
+                class:invalid=move || error().is_some()
+                placeholder=move || t_string!(i18n, opt_replace_pattern_placeholder)
+                prop:value=move || rule().pattern
+                on:input:target=move |ev| update(&|rule| rule.pattern = ev.target().value())
+            />
+            <input
+                type="text"
+                placeholder=move || t_string!(i18n, opt_replace_replacement_placeholder)
+                prop:value=move || rule().replacement
+                on:input:target=move |ev| update(&|rule| rule.replacement = ev.target().value())
+            />
+            <button
+                type="button"
+                title=move || t_string!(i18n, opt_replace_remove)
+                on:click=move |_| {
+                    let mut rules = options.replace_rules().write();
+                    if idx < rules.len() {
+                        rules.remove(idx);
+                    }
+                }
+            >
+                "✖"
+            </button>
+            {move || error().map(|msg| view! { <p class="error">{msg}</p> })}
+        </div>
+    }
+}
+
+#[component]
+fn DropRuleInput(options: Store<Options>, idx: usize) -> impl IntoView {
+    let i18n = use_i18n();
+    let rule = move || {
+        options
+            .drop_rules()
+            .read()
+            .get(idx)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let error = move || rule().compile().err().map(|err| err.to_string());
+    let update = move |f: &dyn Fn(&mut DropRule)| {
+        if let Some(rule) = options.drop_rules().write().get_mut(idx) {
+            f(rule);
+        }
+    };
+
+    view! {
+        <div class="rule">
+            <select
+                prop:value=move || {
+                    let value: &str = rule().field.into();
+                    value
+                }
+                on:change:target=move |ev| {
+                    let value = ev.target().value().parse().unwrap();
+                    update(&|rule| rule.field = value);
+                }
+            >
+                <option value=DropField::Text>{t!(i18n, opt_drop_field_text)}</option>
+                <option value=DropField::Style>{t!(i18n, opt_drop_field_style)}</option>
+                <option value=DropField::Name>{t!(i18n, opt_drop_field_name)}</option>
+            </select>
+            <input
+                type="text"
+                class:invalid=move || error().is_some()
+                placeholder=move || t_string!(i18n, opt_replace_pattern_placeholder)
+                prop:value=move || rule().pattern
+                on:input:target=move |ev| update(&|rule| rule.pattern = ev.target().value())
+            />
+            <button
+                type="button"
+                title=move || t_string!(i18n, opt_replace_remove)
+                on:click=move |_| {
+                    let mut rules = options.drop_rules().write();
+                    if idx < rules.len() {
+                        rules.remove(idx);
+                    }
+                }
+            >
+                "✖"
+            </button>
+            {move || error().map(|msg| view! { <p class="error">{msg}</p> })}
+        </div>
+    }
+}
+
+#[component]
+fn ChineseConvertionOptions() -> impl IntoView {
+    let i18n = use_i18n();
+    view! {
+        <option value=ChineseConvertion::Keep>{t!(i18n, opt_chinese_convert_no)}</option>
+        <option value=ChineseConvertion::ToSimplified>
+            {t!(i18n, opt_chinese_convert_t2s)}
+        </option>
+        <option value=ChineseConvertion::ToTraditional>
+            {t!(i18n, opt_chinese_convert_st2)}
+        </option>
+        <option value=ChineseConvertion::AutoToSimplified>
+            {t!(i18n, opt_chinese_convert_auto_t2s)}
+        </option>
+        <option value=ChineseConvertion::AutoToTraditional>
+            {t!(i18n, opt_chinese_convert_auto_s2t)}
+        </option>
+        <option value=ChineseConvertion::ToTaiwan>
+            {t!(i18n, opt_chinese_convert_s2tw)}
+        </option>
+        <option value=ChineseConvertion::ToTaiwanPhrases>
+            {t!(i18n, opt_chinese_convert_s2twp)}
+        </option>
+        <option value=ChineseConvertion::ToHongKong>
+            {t!(i18n, opt_chinese_convert_s2hk)}
+        </option>
+        <option value=ChineseConvertion::TaiwanToSimplified>
+            {t!(i18n, opt_chinese_convert_tw2s)}
+        </option>
+        <option value=ChineseConvertion::HongKongToSimplified>
+            {t!(i18n, opt_chinese_convert_hk2s)}
+        </option>
+        <option value=ChineseConvertion::ToKyujitai>
+            {t!(i18n, opt_chinese_convert_jp2t)}
+        </option>
+        <option value=ChineseConvertion::ToShinjitai>
+            {t!(i18n, opt_chinese_convert_t2jp)}
+        </option>
+    }
+}
+
+#[component]
+fn OptionRuleInput(options: Store<Options>, idx: usize) -> impl IntoView {
+    let i18n = use_i18n();
+    let rule = move || {
+        options
+            .option_rules()
+            .read()
+            .get(idx)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let update = move |f: &dyn Fn(&mut OptionRule)| {
+        if let Some(rule) = options.option_rules().write().get_mut(idx) {
+            f(rule);
+        }
+    };
+
+    view! {
+        <div class="rule">
+            <input
+                type="text"
+                placeholder=move || t_string!(i18n, opt_rules_pattern_placeholder)
+                prop:value=move || rule().pattern
+                on:input:target=move |ev| update(&|rule| rule.pattern = ev.target().value())
+            />
+            <input
+                type="text"
+                list="charsets"
+                placeholder=move || t_string!(i18n, opt_rules_ass_encoding_placeholder)
+                prop:value=move || rule().ass_charset.unwrap_or_default()
+                on:input:target=move |ev| {
+                    let value = Some(ev.target().value()).filter(|v| !v.is_empty());
+                    update(&|rule| rule.ass_charset = value.clone())
+                }
+            />
+            <select
+                prop:value=move || rule().chinese_convertion.map(<&str>::from).unwrap_or_default()
+                on:change:target=move |ev| {
+                    let value = ev.target().value().parse().ok();
+                    update(&|rule| rule.chinese_convertion = value);
+                }
+            >
+                <option value="">{t!(i18n, opt_rules_no_override)}</option>
+                <ChineseConvertionOptions />
+            </select>
+            <select
+                prop:value=move || rule().line_strip.map(<&str>::from).unwrap_or_default()
+                on:change:target=move |ev| {
+                    let value = ev.target().value().parse().ok();
+                    update(&|rule| rule.line_strip = value);
+                }
+            >
+                <option value="">{t!(i18n, opt_rules_no_override)}</option>
+                <option value=LineStrip::KeepAll>{t!(i18n, opt_lines_all)}</option>
+                <option value=LineStrip::KeepFirst>{t!(i18n, opt_lines_first)}</option>
+                <option value=LineStrip::KeepLast>{t!(i18n, opt_lines_last)}</option>
+            </select>
+            <button
+                type="button"
+                title=move || t_string!(i18n, opt_replace_remove)
+                on:click=move |_| {
+                    let mut rules = options.option_rules().write();
+                    if idx < rules.len() {
+                        rules.remove(idx);
+                    }
+                }
+            >
+                "✖"
+            </button>
+        </div>
+    }
+}
+
+/// Show zero as empty (i.e. unlimited)
+fn display_limit(n: u32) -> String {
+    if n == 0 { String::new() } else { n.to_string() }
+}
@@ -4,14 +4,18 @@ use leptos::prelude::*;
 use leptos_i18n::{t, t_string};
 
 use crate::{
+    Script,
     app::{
         components::DonateBanner,
         i18n::use_i18n,
         task::{Task, TaskState, Tasks},
     },
-    worker::{ConvertError, FormatError},
+    worker::{ConvertError, FileStatus, FileWarning, FormatError, Substitution},
 };
 
+/// Number of substitutions shown in the conversion report of each file
+const REPORT_TOP_N: usize = 10;
+
 #[component]
 pub(crate) fn TaskList(tasks: ReadSignal<Tasks>, set_tasks: WriteSignal<Tasks>) -> impl IntoView {
     let i18n = use_i18n();
@@ -31,7 +35,7 @@ pub(crate) fn TaskList(tasks: ReadSignal<Tasks>, set_tasks: WriteSignal<Tasks>)
             <button
                 class="clear"
                 on:click=move |_| {
-                    *set_remove_pending_count.write() = set_tasks.write().clear_ended_prepare();
+                    *set_remove_pending_count.write() += set_tasks.write().clear_ended_prepare();
                 }
             >
                 {t!(i18n, task_action_clear)}
@@ -72,6 +76,7 @@ fn TaskItem(
             TaskState::Working => ("🟢", t_string!(i18n, task_state_working)),
             TaskState::Done { .. } => ("🎉", t_string!(i18n, task_state_done)),
             TaskState::Error(_) => ("⚠️", t_string!(i18n, task_state_error)),
+            TaskState::Canceled => ("🚫", t_string!(i18n, task_state_canceled)),
         };
         view! { <span class="state">{icon}{" "}{label}</span> }
     };
@@ -101,43 +106,27 @@ fn TaskItem(
         .take_if(|_| n > 1)
     };
 
+    let progress_bar = move || {
+        let progress = task.progress.get()?;
+        let (done, discovered) = (progress.files_done, progress.files_discovered);
+        let size = display_size(progress.bytes_processed);
+        let current = progress.current_file.unwrap_or_default();
+        Some(view! {
+            <div class="progress">
+                <progress max=discovered value=done />
+                <p>
+                    {t!(i18n, task_progress, done, discovered, size)}
+                    <span class="current" title=current.clone()>
+                        {current}
+                    </span>
+                </p>
+            </div>
+        })
+        .take_if(|_| task.state.read().is_working())
+    };
+
     let error_message = move || match task.state.get() {
-        TaskState::Error(err) => view! {
-            <p class="error">
-                "😢"
-                {match err {
-                    ConvertError::NoFile => unreachable!("UI must not pass empty list"),
-                    ConvertError::TooLarge { size, limit } => {
-                        t!(i18n, error_file_limit, size, limit).into_any()
-                    }
-                    ConvertError::FetchDict(msg) => t!(i18n, error_fetch_dict, msg).into_any(),
-                    ConvertError::EncodingLabel(label) => {
-                        t!(i18n, error_encoding_label, label).into_any()
-                    }
-                    ConvertError::EncodingDetect => t!(i18n, error_encoding_detect).into_any(),
-                    ConvertError::Utf16Output => t!(i18n, error_utf16_output).into_any(),
-                    ConvertError::Canceled => t!(i18n, error_canceled).into_any(),
-                    ConvertError::Zip(msg) => t!(i18n, error_zip, msg).into_any(),
-                    ConvertError::JsError { name, msg } => {
-                        t!(i18n, error_js_error, name, msg).into_any()
-                    }
-                    ConvertError::Format(FormatError::NoFormatLine) => {
-                        t!(i18n, error_format_no_format_line).into_any()
-                    }
-                    ConvertError::Format(FormatError::NoFormatLineField(field)) => {
-                        t!(i18n, error_format_no_format_line_field, field=field.to_string())
-                            .into_any()
-                    }
-                    ConvertError::Format(FormatError::NoField(field)) => {
-                        t!(i18n, error_format_no_field, field=field.to_string()).into_any()
-                    }
-                    ConvertError::Format(FormatError::Time(string)) => {
-                        t!(i18n, error_format_time, string).into_any()
-                    }
-                }}
-            </p>
-        }
-        .into_any(),
+        TaskState::Error(err) => view! { <p class="error">"😢"{error_text(err)}</p> }.into_any(),
         TaskState::Done(file) if file.meta.has_error() => {
             let meta_ = file.meta.clone();
             let input = move || display_encodings(meta_.input_encoding.clone());
@@ -158,7 +147,153 @@ fn TaskItem(
             }
             .into_any()
         }
-        TaskState::Done { .. } | TaskState::Pending { .. } | TaskState::Working => ().into_any(),
+        TaskState::Done { .. }
+        | TaskState::Pending { .. }
+        | TaskState::Working
+        | TaskState::Canceled => ().into_any(),
+    };
+
+    let info_message = move || match task.state.get() {
+        TaskState::Done(file) => {
+            let meta = &file.meta;
+            let n = meta.dropped_cues;
+            let dropped =
+                (n > 0).then(|| info_line("🧹", t!(i18n, info_dropped_cues, n), Vec::new()));
+            let count = |script| meta.scripts.iter().filter(|s| s.script == script).count();
+            let (simplified, traditional, mixed) = (
+                count(Script::Simplified),
+                count(Script::Traditional),
+                count(Script::Mixed),
+            );
+            let detected = (simplified + traditional + mixed > 0).then(|| {
+                let text = t!(i18n, info_scripts, simplified, traditional, mixed);
+                info_line("🔍", text, Vec::new())
+            });
+            let n = meta.scripts.iter().filter(|s| !s.converted).count();
+            let skipped =
+                (n > 0).then(|| info_line("⏭️", t!(i18n, info_scripts_skipped, n), Vec::new()));
+            let details = meta
+                .rules
+                .iter()
+                .map(|r| format!("{} ← #{} {}", r.filename, r.rule + 1, r.pattern))
+                .collect::<Vec<_>>();
+            let n = details.len();
+            let matched =
+                (n > 0).then(|| info_line("📐", t!(i18n, info_rules_matched, n), details));
+            let details = meta
+                .charset_hints
+                .iter()
+                .map(|h| {
+                    let mut line = format!("{} ← {}", h.filename, h.encoding);
+                    if h.fallback {
+                        line += &format!(" ({})", t_string!(i18n, info_charset_hint_fallback));
+                    }
+                    line
+                })
+                .collect::<Vec<_>>();
+            let n = details.len();
+            let hinted = (n > 0).then(|| info_line("🔤", t!(i18n, info_charset_hints, n), details));
+            let details = meta
+                .detections
+                .iter()
+                .filter(|d| !d.sure)
+                .map(|d| {
+                    let candidates = d
+                        .candidates
+                        .iter()
+                        .map(|c| {
+                            if c.sure {
+                                let sure = t_string!(i18n, info_encoding_candidate_sure);
+                                format!("{} ({})", c.encoding, sure)
+                            } else {
+                                c.encoding.clone()
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("{}: {}", d.filename, candidates)
+                })
+                .collect::<Vec<_>>();
+            let n = details.len();
+            let uncertain =
+                (n > 0).then(|| info_line("❔", t!(i18n, info_encoding_uncertain, n), details));
+            let details = meta
+                .detections
+                .iter()
+                .filter_map(|d| Some(format!("{} ← {}", d.filename, d.fallback.as_ref()?)))
+                .collect::<Vec<_>>();
+            let n = details.len();
+            let fallback =
+                (n > 0).then(|| info_line("🔁", t!(i18n, info_encoding_fallback, n), details));
+            let details = meta
+                .repairs
+                .iter()
+                .map(|r| format!("{} ← {}", r.filename, r.encoding))
+                .collect::<Vec<_>>();
+            let n = details.len();
+            let repaired =
+                (n > 0).then(|| info_line("🩹", t!(i18n, info_mojibake_repaired, n), details));
+            Some(
+                view! { {dropped}{detected}{skipped}{matched}{hinted}{uncertain}{fallback}{repaired} },
+            )
+        }
+        _ => None,
+    };
+
+    let conversion_report = move || match task.state.get() {
+        TaskState::Done(file) if !file.meta.reports.is_empty() => {
+            let multiple = file.meta.reports.len() > 1;
+            let reports = file.meta.reports.into_iter().map(move |report| {
+                view! {
+                    {multiple.then(|| view! { <p class="filename">{report.filename}</p> })}
+                    <ol>{report_items(report.substitutions)}</ol>
+                }
+            });
+            Some(view! {
+                <details class="report">
+                    <summary>{t!(i18n, task_report_summary)}</summary>
+                    {reports.collect_view()}
+                </details>
+            })
+        }
+        _ => None,
+    };
+
+    let file_status = move || {
+        let files = match task.state.get() {
+            TaskState::Done(file) if file.files.len() > 1 => file.files,
+            TaskState::Error(ConvertError::AllFailed(files)) => files,
+            _ => return None,
+        };
+        let failed = files.iter().filter(|f| f.error.is_some()).count();
+        let converted = files.len() - failed;
+        // expand if anything failed
+        let open = failed > 0;
+        Some(view! {
+            <details class="file-status" open=open>
+                <summary>{t!(i18n, task_file_status_summary, converted, failed)}</summary>
+                <ol>{files.into_iter().map(file_status_item).collect_view()}</ol>
+            </details>
+        })
+    };
+
+    let cancel_button = move || {
+        let cancelable = task.state.with(|s| s.is_pending() || s.is_working());
+        Some(view! {
+            <button
+                class="cancel"
+                title=move || t_string!(i18n, task_action_cancel)
+                on:click=move |_| {
+                    // pending task is removed right away
+                    if task.cancel() && task.mark_removing() {
+                        *set_remove_pending_count.write() += 1;
+                    }
+                }
+            >
+                "✖"
+            </button>
+        })
+        .take_if(|_| cancelable)
     };
 
     let download_link = move || match task.state.get() {
@@ -182,6 +317,7 @@ fn TaskItem(
             class:working=move || task.state.read().is_working()
             class:done=move || task.state.read().is_done()
             class:error=move || task.state.read().is_error()
+            class:canceled=move || task.state.read().is_canceled()
             class:removing=move || task.is_removing.get()
             on:animationend=move |ev| {
                 if ev.animation_name() == "fade-out" {
@@ -192,13 +328,114 @@ fn TaskItem(
             <div class="columns">
                 <div class="state-and-title">{state_label}{title}</div>
                 {move || Some(download_link).take_if(|_| task.state.read().is_done())}
+                {cancel_button}
             </div>
             {move || more_files().map(|m| view! { <div class="more-files">{m}</div> })}
+            {progress_bar}
             {error_message}
+            {info_message}
+            {file_status}
+            {conversion_report}
         </li>
     }
 }
 
+fn report_items(substitutions: Vec<Substitution>) -> impl IntoView {
+    let i18n = use_i18n();
+    substitutions
+        .into_iter()
+        .take(REPORT_TOP_N)
+        .map(|sub| {
+            let (from, to, count) = (sub.from, sub.to, sub.count);
+            let cues = sub.cues.iter().map(|n| format!("#{n}")).collect::<Vec<_>>();
+            let cues = cues.join(", ");
+            view! { <li>{t!(i18n, task_report_item, from, to, count, cues)}</li> }
+        })
+        .collect_view()
+}
+
+/// Line of task info, with details of each file (if any) as its tooltip
+fn info_line(icon: &'static str, text: impl IntoView, details: Vec<String>) -> impl IntoView {
+    let title = Some(details.join("\n")).filter(|title| !title.is_empty());
+    view! { <p class="info" title=title>{icon}{text}</p> }
+}
+
+fn file_status_item(status: FileStatus) -> impl IntoView {
+    let i18n = use_i18n();
+    let failed = status.error.is_some();
+    let icon = match (failed, status.warnings.is_empty()) {
+        (true, _) => "❌",
+        (false, false) => "⚠️",
+        (false, true) => "✅",
+    };
+    let detail = match status.error {
+        Some(err) => error_text(err),
+        None => {
+            let encodings = format!(
+                "{} → {}",
+                status.input_encoding.unwrap_or_default(),
+                status.output_encoding.unwrap_or_default()
+            );
+            let warnings = status.warnings.into_iter().map(|warning| match warning {
+                FileWarning::Decoding => t!(i18n, task_file_warning_decoding).into_any(),
+                FileWarning::Encoding => t!(i18n, task_file_warning_encoding).into_any(),
+            });
+            view! { {encodings}{warnings.map(|w| view! { " · "{w} }).collect_view()} }.into_any()
+        }
+    };
+    let path = status.path;
+    view! {
+        <li class:failed=failed title=path.clone()>
+            {icon}" "{path}": "{detail}
+        </li>
+    }
+}
+
+fn error_text(err: ConvertError) -> AnyView {
+    let i18n = use_i18n();
+    match err {
+        ConvertError::NoFile => unreachable!("UI must not pass empty list"),
+        ConvertError::TooLarge { size, limit } => {
+            t!(i18n, error_file_limit, size, limit).into_any()
+        }
+        ConvertError::FetchDict(msg) => t!(i18n, error_fetch_dict, msg).into_any(),
+        ConvertError::EncodingLabel(label) => t!(i18n, error_encoding_label, label).into_any(),
+        ConvertError::EncodingDetect => t!(i18n, error_encoding_detect).into_any(),
+        ConvertError::Canceled => t!(i18n, error_canceled).into_any(),
+        ConvertError::Zip(msg) => t!(i18n, error_zip, msg).into_any(),
+        ConvertError::AllFailed(files) => {
+            let n = files.len();
+            t!(i18n, error_all_failed, n).into_any()
+        }
+        ConvertError::Regex { pattern, msg } => t!(i18n, error_regex, pattern, msg).into_any(),
+        ConvertError::JsError { name, msg } => t!(i18n, error_js_error, name, msg).into_any(),
+        ConvertError::Format(FormatError::NoFormatLine) => {
+            t!(i18n, error_format_no_format_line).into_any()
+        }
+        ConvertError::Format(FormatError::NoFormatLineField(field)) => t!(
+            i18n,
+            error_format_no_format_line_field,
+            field = field.to_string()
+        )
+        .into_any(),
+        ConvertError::Format(FormatError::NoField(field)) => {
+            t!(i18n, error_format_no_field, field = field.to_string()).into_any()
+        }
+        ConvertError::Format(FormatError::Time(string)) => {
+            t!(i18n, error_format_time, string).into_any()
+        }
+    }
+}
+
+/// Format bytes in KiB/MiB
+fn display_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
 fn display_encodings(encodings: HashSet<String>) -> String {
     match encodings.len() {
         0 => "[]".to_string(),
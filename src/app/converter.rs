@@ -1,32 +1,73 @@
 use futures::{
     channel::oneshot::{Receiver, channel},
-    lock::{Mutex, MutexGuard},
+    future::{Either, select, select_all},
+    lock::{Mutex, OwnedMutexGuard},
 };
 use send_wrapper::SendWrapper;
-use std::sync::Arc;
+use std::sync::{self, Arc};
 use wasm_bindgen::prelude::*;
 use web_sys::{File, MessageEvent, Worker, WorkerOptions, WorkerType, window};
 
 use crate::{
-    ConvertMeta, FileWrap, Options, TaskRequest, TaskResult, WorkerMessage, worker::ConvertError,
+    ConvertMeta, FileWrap, Options, TaskProgress, TaskRequest, TaskResult, UserDict, WorkerMessage,
+    worker::{ConvertError, FileStatus},
 };
 
 use super::task::BlobUrl;
 
+/// Upper bound of the pool size. Every worker loads its own copy of the
+/// OpenCC dicts (a few MiB each), so memory grows with the pool.
+pub(crate) const MAX_WORKERS: usize = 4;
+
+/// Pool of workers, each converts one task at a time
 #[derive(Debug, Clone)]
 pub(crate) struct Converter {
-    inner: Arc<Mutex<Inner>>,
+    workers: Arc<sync::Mutex<Vec<Arc<Mutex<Inner>>>>>,
 }
 
 impl Converter {
     pub(crate) fn new() -> Self {
+        // spawn the first worker early, others on demand
+        let worker = Arc::new(Mutex::new(Inner::new()));
         Self {
-            inner: Mutex::new(Inner::new()).into(),
+            workers: Arc::new(sync::Mutex::new(vec![worker])),
+        }
+    }
+
+    /// Number of logical processors, capped by `max_workers` (0 for no
+    /// cap) & [`MAX_WORKERS`]
+    pub(crate) fn pool_size(max_workers: u32) -> usize {
+        let cores = window()
+            .map(|w| w.navigator().hardware_concurrency() as usize)
+            .unwrap_or(1)
+            .clamp(1, MAX_WORKERS);
+        match max_workers {
+            0 => cores,
+            n => cores.min(n as usize),
         }
     }
 
-    pub(crate) async fn lock(&self) -> MutexGuard<'_, Inner> {
-        self.inner.lock().await
+    /// Take a free worker, or spawn a new one if the pool is not full
+    pub(crate) async fn acquire(&self, pool_size: usize) -> OwnedMutexGuard<Inner> {
+        let locks: Vec<_> = {
+            let mut workers = self.workers.lock().unwrap();
+            if let Some(guard) = workers.iter().find_map(|w| w.try_lock_owned()) {
+                return guard;
+            }
+            if workers.len() < pool_size {
+                log::debug!("converter: grow pool to {}", workers.len() + 1);
+                let worker = Arc::new(Mutex::new(Inner::new()));
+                workers.push(worker.clone());
+                vec![Box::pin(worker.lock_owned())]
+            } else {
+                // all busy, wait for whichever frees first
+                workers
+                    .iter()
+                    .map(|w| Box::pin(w.clone().lock_owned()))
+                    .collect()
+            }
+        };
+        select_all(locks).await.0
     }
 }
 
@@ -72,20 +113,34 @@ impl Inner {
     pub(crate) async fn convert(
         &mut self,
         options: Options,
+        user_dict: UserDict,
         files: Vec<File>,
+        on_progress: impl Fn(TaskProgress) + 'static,
+        mut cancel: Receiver<()>,
     ) -> Result<ConvertedFile, ConvertError> {
         // wait for worker ready
         if let Some(ready) = self.ready.take() {
             log::debug!("convert: wait for worker ready");
             ready.await?;
         }
+        // canceled while waiting for the worker, it's still idle and reusable
+        if let Ok(Some(())) = cancel.try_recv() {
+            log::debug!("convert: canceled before request");
+            return Err(ConvertError::Canceled);
+        }
         log::debug!("convert: {:?} files", files.len());
         // setup event listener
         let (result_tx, result_rx) = channel();
+        let mut result_tx = Some(result_tx);
         let worker = self.worker.clone().take();
-        let on_message = Closure::once(move |ev: MessageEvent| {
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |ev: MessageEvent| {
             match serde_wasm_bindgen::from_value(ev.data()) {
-                Ok(WorkerMessage::TaskDone(result)) => result_tx.send(result).unwrap(),
+                Ok(WorkerMessage::TaskProgress(progress)) => return on_progress(progress),
+                Ok(WorkerMessage::TaskDone(result)) => {
+                    if let Some(tx) = result_tx.take() {
+                        tx.send(result).unwrap();
+                    }
+                }
                 Ok(msg) => log::warn!("unexpected message {:?}", msg),
                 Err(err) => log::error!("failed to parse message {:?}", err),
             }
@@ -93,15 +148,35 @@ impl Inner {
         });
         let worker = self.worker.clone().take();
         worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
-        on_message.forget();
         // send request
         let request = TaskRequest {
             options,
+            user_dict,
             files: files.into_iter().map(FileWrap).collect(),
         };
-        worker.post_message(&serde_wasm_bindgen::to_value(&request).unwrap())?;
-        // wait response
-        result_rx.await?.map(|r| r.into())
+        if let Err(err) = worker.post_message(&serde_wasm_bindgen::to_value(&request).unwrap()) {
+            worker.set_onmessage(None);
+            return Err(err.into());
+        }
+        // wait response, the listener has been removed once done
+        let result = match select(result_rx, cancel).await {
+            Either::Left((result, _)) => result,
+            Either::Right((Ok(()), _)) => {
+                // no way to interrupt the worker but terminate it
+                log::debug!("convert: canceled, respawn worker");
+                worker.set_onmessage(None);
+                self.worker.terminate();
+                *self = Self::new();
+                return Err(ConvertError::Canceled);
+            }
+            // cancel sender dropped, keep waiting
+            Either::Right((Err(_), result_rx)) => result_rx.await,
+        };
+        // the listener may be still there (e.g. result channel canceled),
+        // detach it before the closure is freed
+        worker.set_onmessage(None);
+        drop(on_message);
+        result?.map(|r| r.into())
     }
 }
 
@@ -110,6 +185,8 @@ pub(crate) struct ConvertedFile {
     pub(crate) url: BlobUrl,
     pub(crate) name: String,
     pub(crate) meta: ConvertMeta,
+    /// Status of each file
+    pub(crate) files: Vec<FileStatus>,
 }
 
 impl From<TaskResult> for ConvertedFile {
@@ -118,6 +195,7 @@ impl From<TaskResult> for ConvertedFile {
             url: BlobUrl::new(result.file_url),
             name: result.filename,
             meta: result.meta,
+            files: result.files,
         }
     }
 }
@@ -1,16 +1,17 @@
-use leptos::prelude::*;
+use leptos::{prelude::*, task::spawn_local};
 use leptos_i18n::{t, t_string};
 use leptos_meta::Title;
 use reactive_stores::Store;
 
 use crate::{
-    Options,
+    Options, UserDict,
     app::{
         components::{DonateLink, FileInput, OptionsForm, TaskList, ToggleBar},
         converter::Converter,
         i18n::use_i18n,
         task::{Task, Tasks},
     },
+    worker::ConvertError,
 };
 
 const GITHUB_LINK: &str = "https://github.com/sorz/asstosrt-wasm";
@@ -20,30 +21,40 @@ const GITHUB_LINK: &str = "https://github.com/sorz/asstosrt-wasm";
 pub fn Home() -> impl IntoView {
     let i18n = use_i18n();
     let options = Store::new(Options::load_from_storage());
+    let user_dict = Store::new(UserDict::load_from_storage());
     let (tasks, set_tasks) = signal(Tasks::default());
     let converter: Converter = use_context().expect("converter not found");
 
-    let convert = Action::new_local(move |task: &Task| {
-        let task = *task;
+    let start_task = move |task: Task, pool_size: usize| {
+        // canceled before scheduled
+        let Some((files, cancel)) = task.set_working() else {
+            return;
+        };
         let options = options.read_untracked().clone();
+        let user_dict = user_dict.read_untracked().clone();
         let converter = converter.clone();
-        async move {
-            let mut conv = converter.lock().await;
-            let files = task.set_working().expect("try to work on non-pending task");
-            match conv.convert(options, files).await {
+        spawn_local(async move {
+            let mut conv = converter.acquire(pool_size).await;
+            let on_progress = move |progress| task.set_progress(progress);
+            let result = conv
+                .convert(options, user_dict, files, on_progress, cancel)
+                .await;
+            match result {
                 Ok(file) => task.set_done(file),
+                Err(ConvertError::Canceled) => task.set_canceled(),
                 Err(msg) => task.set_error(msg),
             }
-        }
-    });
-    // Schedule task
+        });
+    };
+    // Schedule pending tasks to free workers
     Effect::new(move |_| {
+        let pool_size = Converter::pool_size(options.max_workers().get());
         let tasks = tasks.read();
-        if !tasks.any_working_task() {
-            // do it in serial
-            if let Some(task) = tasks.get_next_pending() {
-                convert.dispatch_local(task);
-            }
+        let free = pool_size.saturating_sub(tasks.working_count());
+        let pending: Vec<_> = tasks.pending().take(free).collect();
+        drop(tasks);
+        for task in pending {
+            start_task(task, pool_size);
         }
     });
     // Save options
@@ -52,6 +63,12 @@ pub fn Home() -> impl IntoView {
             log::error!("failed to save options: {:?}", err);
         }
     });
+    // Save user dict
+    Effect::new(move |_| {
+        if let Err(err) = user_dict.read().save_to_storage() {
+            log::error!("failed to save user dict: {:?}", err);
+        }
+    });
 
     view! {
         <Title text=move || t_string!(i18n, html_title) />
@@ -78,10 +95,16 @@ pub fn Home() -> impl IntoView {
                     </ul>
                 }
             }>
-                <details class="options" prop:open=move || !options.read_untracked().is_default()>
+                <details
+                    class="options"
+                    prop:open=move || {
+                        !options.read_untracked().is_default()
+                            || !user_dict.read_untracked().is_empty()
+                    }
+                >
                     <summary>{t!(i18n, opt_title)}</summary>
                     <form>
-                        <OptionsForm options=options />
+                        <OptionsForm options user_dict />
                     </form>
                 </details>
                 <FileInput on_files=move |files| {
@@ -11,6 +11,7 @@ pub(crate) enum Key {
     Locale,
     Theme,
     Options,
+    UserDict,
     HideDonateUntil,
 }
 
@@ -1,12 +1,13 @@
 use std::{fmt::Display, mem, sync::Arc};
 
+use futures::channel::oneshot::{Receiver, Sender, channel};
 use leptos::prelude::*;
 use strum::EnumIs;
 use uuid::Uuid;
 use web_sys::{File, Url};
 
 use super::converter::ConvertedFile;
-use crate::worker::ConvertError;
+use crate::{TaskProgress, worker::ConvertError};
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct Tasks(pub(crate) Vec<Task>);
@@ -20,18 +21,14 @@ impl Tasks {
         self.0.is_empty()
     }
 
-    /// Set is_removing flag for all done/error tasks
-    /// Return the number of modified tasks
+    /// Set is_removing flag for all done/error/canceled tasks
+    /// Return the number of newly marked tasks
     pub(crate) fn clear_ended_prepare(&mut self) -> usize {
-        let mut n = 0;
-        for task in self.0.iter() {
-            let state = task.state.read_untracked();
-            if state.is_done() || state.is_error() {
-                *task.is_removing.write() = true;
-                n += 1
-            }
-        }
-        n
+        self.0
+            .iter()
+            .filter(|task| task.state.with_untracked(TaskState::is_ended))
+            .filter(|task| task.mark_removing())
+            .count()
     }
 
     // Clear all tasks which has set is_removing flag
@@ -39,21 +36,25 @@ impl Tasks {
         self.retain(|task| !task.is_removing.get_untracked());
     }
 
-    /// Check if any task is done or errored
+    /// Check if any task is done, errored or canceled
     pub(crate) fn any_ended(&self) -> bool {
         self.0
             .iter()
-            .any(|task| task.state.with(|state| state.is_done() || state.is_error()))
+            .any(|task| task.state.with(TaskState::is_ended))
     }
 
-    pub(crate) fn any_working_task(&self) -> bool {
-        self.0.iter().any(|task| task.state.read().is_working())
+    pub(crate) fn working_count(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|task| task.state.read().is_working())
+            .count()
     }
 
-    pub(crate) fn get_next_pending(&self) -> Option<Task> {
+    /// Pending tasks in the order they were added
+    pub(crate) fn pending(&self) -> impl Iterator<Item = Task> {
         self.0
             .iter()
-            .find(|task| task.state.read().is_pending())
+            .filter(|task| task.state.read().is_pending())
             .copied()
     }
 
@@ -63,6 +64,8 @@ impl Tasks {
             if !retain {
                 task.filenames.dispose();
                 task.state.dispose();
+                task.progress.dispose();
+                task.cancel.dispose();
                 task.is_removing.dispose();
             }
             retain
@@ -75,6 +78,10 @@ pub(crate) struct Task {
     pub(crate) id: Uuid,
     pub(crate) filenames: RwSignal<Vec<String>>,
     pub(crate) state: RwSignal<TaskState, LocalStorage>,
+    /// Latest progress reported by the worker
+    pub(crate) progress: RwSignal<Option<TaskProgress>>,
+    /// Set when working, to stop the worker
+    cancel: StoredValue<Option<Sender<()>>, LocalStorage>,
     /// To be removed from task list (waiting for animation end)
     pub(crate) is_removing: RwSignal<bool>,
 }
@@ -85,6 +92,13 @@ pub(crate) enum TaskState {
     Working,
     Done(Arc<ConvertedFile>),
     Error(ConvertError),
+    Canceled,
+}
+
+impl TaskState {
+    pub(crate) fn is_ended(&self) -> bool {
+        self.is_done() || self.is_error() || self.is_canceled()
+    }
 }
 
 impl Task {
@@ -94,17 +108,66 @@ impl Task {
             id: Uuid::new_v4(),
             filenames: RwSignal::new(filenames),
             state: RwSignal::new_local(TaskState::Pending { files }),
+            progress: RwSignal::new(None),
+            cancel: StoredValue::new_local(None),
             is_removing: RwSignal::new(false),
         }
     }
 
-    pub(crate) fn set_working(&self) -> Option<Vec<File>> {
-        self.state
-            .try_update(|state| match mem::replace(state, TaskState::Working) {
-                TaskState::Pending { files } => Some(files),
-                _ => None,
+    /// Return the files & a receiver that resolves if canceled
+    pub(crate) fn set_working(&self) -> Option<(Vec<File>, Receiver<()>)> {
+        let files = self
+            .state
+            .try_update(|state| {
+                // keep other states (e.g. canceled) untouched
+                let TaskState::Pending { files } = state else {
+                    return None;
+                };
+                let files = mem::take(files);
+                *state = TaskState::Working;
+                Some(files)
+            })
+            .flatten()?;
+        let (cancel_tx, cancel_rx) = channel();
+        self.cancel.set_value(Some(cancel_tx));
+        Some((files, cancel_rx))
+    }
+
+    /// Cancel pending or working task.
+    /// Return true if it was pending, thus can be removed right away.
+    pub(crate) fn cancel(&self) -> bool {
+        let was_pending = self
+            .state
+            .try_update(|state| {
+                let pending = state.is_pending();
+                if pending {
+                    *state = TaskState::Canceled;
+                }
+                pending
             })
-            .flatten()
+            .unwrap_or(false);
+        if let Some(cancel_tx) = self.cancel.try_update_value(Option::take).flatten() {
+            // the working task will be set to canceled when worker stopped
+            let _ = cancel_tx.send(());
+        }
+        was_pending
+    }
+
+    /// Set is_removing flag, return false if it has been set already
+    pub(crate) fn mark_removing(&self) -> bool {
+        let marked = !self.is_removing.get_untracked();
+        if marked {
+            self.is_removing.set(true);
+        }
+        marked
+    }
+
+    pub(crate) fn set_canceled(&self) {
+        self.state.set(TaskState::Canceled);
+    }
+
+    pub(crate) fn set_progress(&self, progress: TaskProgress) {
+        self.progress.set(Some(progress));
     }
 
     pub(crate) fn set_done(&self, file: ConvertedFile) {
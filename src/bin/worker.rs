@@ -21,13 +21,20 @@ fn main() {
         };
         let scope = scope_.clone();
         spawn_local(async move {
-            let result = do_conversion_task(request).await;
-            let result = serde_wasm_bindgen::to_value(&WorkerMessage::TaskDone(result)).unwrap();
-            scope.post_message(&result).unwrap();
+            let on_progress =
+                |progress| post_message(&scope, WorkerMessage::TaskProgress(progress));
+            let result = do_conversion_task(request, on_progress).await;
+            post_message(&scope, WorkerMessage::TaskDone(result));
         });
     });
     scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
-    let ready = serde_wasm_bindgen::to_value(&WorkerMessage::WorkerReady).unwrap();
-    scope.post_message(&ready).expect("failed to post message");
+    post_message(&scope, WorkerMessage::WorkerReady);
     on_message.forget();
 }
+
+fn post_message(scope: &DedicatedWorkerGlobalScope, message: WorkerMessage) {
+    let message = serde_wasm_bindgen::to_value(&message).unwrap();
+    scope
+        .post_message(&message)
+        .expect("failed to post message");
+}
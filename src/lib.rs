@@ -2,25 +2,56 @@
 use std::borrow::Cow;
 
 use app::storage::{self, Key};
+use lazy_static::lazy_static;
 use leptos::prelude::*;
 use reactive_stores::Store;
+use regex_lite::Regex;
 use serde::{Deserialize, Serialize};
 use strum::{EnumString, IntoStaticStr};
+use unicode_normalization::UnicodeNormalization;
 use wasm_bindgen::JsValue;
 use web_sys::File;
-use worker::{ConvertError, ConvertMeta};
+use worker::{ConvertError, ConvertMeta, FileStatus};
 
 pub mod app;
 pub mod worker;
 
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumString, IntoStaticStr,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    Serialize,
+    Deserialize,
+    EnumString,
+    IntoStaticStr,
 )]
 pub enum ChineseConvertion {
     #[default]
     Keep,
     ToSimplified,
     ToTraditional,
+    /// Same as ToSimplified but skip files already in Simplified Chinese
+    AutoToSimplified,
+    /// Same as ToTraditional but skip files already in Traditional Chinese
+    AutoToTraditional,
+    /// OpenCC s2tw
+    ToTaiwan,
+    /// OpenCC s2twp, with Taiwan idioms
+    ToTaiwanPhrases,
+    /// OpenCC s2hk
+    ToHongKong,
+    /// OpenCC tw2s
+    TaiwanToSimplified,
+    /// OpenCC hk2s
+    HongKongToSimplified,
+    /// OpenCC jp2t, Japanese Shinjitai to Kyujitai (traditional forms)
+    ToKyujitai,
+    /// OpenCC t2jp, traditional forms to Japanese Shinjitai
+    ToShinjitai,
 }
 
 impl IntoAttributeValue for ChineseConvertion {
@@ -32,11 +63,83 @@ impl IntoAttributeValue for ChineseConvertion {
 }
 
 impl ChineseConvertion {
-    pub fn dict_name(&self) -> Option<&'static str> {
+    /// OpenCC dicts to be applied in sequence
+    pub fn dict_chain(&self) -> &'static [&'static str] {
+        match self {
+            Self::Keep => &[],
+            Self::ToSimplified | Self::AutoToSimplified => &["dict/t2s.bin"],
+            Self::ToTraditional | Self::AutoToTraditional => &["dict/s2t.bin"],
+            Self::ToTaiwan => &["dict/s2t.bin", "dict/t2tw.bin"],
+            Self::ToTaiwanPhrases => &["dict/s2t.bin", "dict/t2twp.bin", "dict/t2tw.bin"],
+            Self::ToHongKong => &["dict/s2t.bin", "dict/t2hk.bin"],
+            Self::TaiwanToSimplified => &["dict/tw2t.bin", "dict/t2s.bin"],
+            Self::HongKongToSimplified => &["dict/hk2t.bin", "dict/t2s.bin"],
+            Self::ToKyujitai => &["dict/jp2t.bin"],
+            Self::ToShinjitai => &["dict/t2jp.bin"],
+        }
+    }
+
+    /// Chinese script of the converted text, None if not a Chinese convertion
+    pub fn target_script(&self) -> Option<Script> {
         match self {
-            Self::Keep => None,
-            Self::ToSimplified => Some("t2s.txt"),
-            Self::ToTraditional => Some("s2t.txt"),
+            Self::Keep | Self::ToKyujitai | Self::ToShinjitai => None,
+            Self::ToSimplified
+            | Self::AutoToSimplified
+            | Self::TaiwanToSimplified
+            | Self::HongKongToSimplified => Some(Script::Simplified),
+            Self::ToTraditional
+            | Self::AutoToTraditional
+            | Self::ToTaiwan
+            | Self::ToTaiwanPhrases
+            | Self::ToHongKong => Some(Script::Traditional),
+        }
+    }
+
+    /// Skip files that already in the target script
+    pub fn is_auto(&self) -> bool {
+        matches!(self, Self::AutoToSimplified | Self::AutoToTraditional)
+    }
+
+    /// Dicts to be applied on text known to be in `source` script. If it's
+    /// already in the target script, only regional variant steps are kept.
+    pub fn dict_chain_from(&self, source: Option<Script>) -> &'static [&'static str] {
+        let chain = self.dict_chain();
+        match (source, self.target_script()) {
+            // s2t goes first, followed by variant steps
+            (Some(Script::Traditional), Some(Script::Traditional)) => &chain[1..],
+            // t2s goes last, variant steps before it are for traditional text
+            (Some(Script::Simplified), Some(Script::Simplified)) => &[],
+            _ => chain,
+        }
+    }
+}
+
+/// Chinese script detected from the content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Script {
+    Simplified,
+    Traditional,
+    Mixed,
+    /// No (or too few) Chinese characters to tell
+    Unknown,
+}
+
+impl Script {
+    /// Ratio of minority hits that still considered as a single script
+    const NOISE_RATIO: f32 = 0.1;
+
+    /// Decide script from the number of characters that only appear in
+    /// Simplified (`simplified`) or Traditional (`traditional`) text
+    pub fn from_hits(simplified: usize, traditional: usize) -> Self {
+        let total = simplified + traditional;
+        if total == 0 {
+            Self::Unknown
+        } else if (traditional as f32) <= (total as f32) * Self::NOISE_RATIO {
+            Self::Simplified
+        } else if (simplified as f32) <= (total as f32) * Self::NOISE_RATIO {
+            Self::Traditional
+        } else {
+            Self::Mixed
         }
     }
 }
@@ -79,14 +182,309 @@ impl LineStrip {
     }
 }
 
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumString, IntoStaticStr,
+)]
+pub enum LineEnding {
+    #[default]
+    Crlf,
+    Lf,
+}
+
+impl IntoAttributeValue for LineEnding {
+    type Output = &'static str;
+
+    fn into_attribute_value(self) -> Self::Output {
+        self.into()
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumString, IntoStaticStr,
+)]
+pub enum ReplaceStage {
+    #[default]
+    BeforeConvertion,
+    AfterConvertion,
+}
+
+impl IntoAttributeValue for ReplaceStage {
+    type Output = &'static str;
+
+    fn into_attribute_value(self) -> Self::Output {
+        self.into()
+    }
+}
+
+/// Regex find & replace rule on cue text
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ReplaceRule {
+    pub pattern: String,
+    /// May refer capture groups with `$1`, `${name}`, etc.
+    pub replacement: String,
+}
+
+impl ReplaceRule {
+    pub(crate) fn compile(&self) -> Result<Regex, regex_lite::Error> {
+        Regex::new(&self.pattern)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumString, IntoStaticStr,
+)]
+pub enum DropField {
+    #[default]
+    Text,
+    Style,
+    Name,
+}
+
+impl IntoAttributeValue for DropField {
+    type Output = &'static str;
+
+    fn into_attribute_value(self) -> Self::Output {
+        self.into()
+    }
+}
+
+/// Drop the whole cue if its `field` matches `pattern`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct DropRule {
+    pub field: DropField,
+    pub pattern: String,
+}
+
+impl DropRule {
+    pub(crate) fn compile(&self) -> Result<Regex, regex_lite::Error> {
+        Regex::new(&self.pattern)
+    }
+}
+
+/// Override some options for files whose path matches `pattern`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct OptionRule {
+    /// Glob pattern (`*`, `?` & `**`), matched against the filename if it
+    /// contains no `/`, or the whole path in zip otherwise
+    pub pattern: String,
+    pub ass_charset: Option<String>,
+    pub chinese_convertion: Option<ChineseConvertion>,
+    pub line_strip: Option<LineStrip>,
+}
+
+impl OptionRule {
+    /// Options with overrides applied
+    pub(crate) fn apply(&self, options: &Options) -> Options {
+        let mut options = options.clone();
+        if let Some(charset) = &self.ass_charset {
+            options.ass_charset = charset.clone();
+        }
+        if let Some(convertion) = self.chinese_convertion {
+            options.chinese_convertion = convertion;
+        }
+        if let Some(line_strip) = self.line_strip {
+            options.line_strip = line_strip;
+        }
+        options
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumString, IntoStaticStr,
+)]
+pub enum NormalForm {
+    #[default]
+    Keep,
+    Nfc,
+    Nfkc,
+}
+
+impl IntoAttributeValue for NormalForm {
+    type Output = &'static str;
+
+    fn into_attribute_value(self) -> Self::Output {
+        self.into()
+    }
+}
+
+/// Unicode normalization & width folding, done before any other text map
+#[derive(Debug, Clone, Copy, Store, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TextNormalization {
+    pub form: NormalForm,
+    /// Full-width Latin letters & digits to half-width.
+    /// Full-width punctuations are left untouched as they are common in CJK text.
+    pub fullwidth_alnum: bool,
+    /// Half-width katakana to full-width
+    pub halfwidth_katakana: bool,
+    /// Remove zero-width space, non-joiner, word joiner & BOM
+    pub zero_width: bool,
+}
+
+impl TextNormalization {
+    fn is_enabled(&self) -> bool {
+        self.form != NormalForm::Keep
+            || self.fullwidth_alnum
+            || self.halfwidth_katakana
+            || self.zero_width
+    }
+
+    pub(crate) fn normalize<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        if !self.is_enabled() {
+            return text;
+        }
+        let text: Cow<str> = match self.form {
+            NormalForm::Keep => text,
+            NormalForm::Nfc => text.nfc().collect::<String>().into(),
+            NormalForm::Nfkc => text.nfkc().collect::<String>().into(),
+        };
+        let mut output = String::with_capacity(text.len());
+        // compose dakuten & handakuten along with the preceding katakana
+        let mut katakana = String::new();
+        for c in text.chars() {
+            if self.halfwidth_katakana && ('\u{FF61}'..='\u{FF9F}').contains(&c) {
+                katakana.push(c);
+                continue;
+            }
+            if !katakana.is_empty() {
+                output.extend(katakana.nfkc());
+                katakana.clear();
+            }
+            match c {
+                '\u{FF10}'..='\u{FF19}' | '\u{FF21}'..='\u{FF3A}' | '\u{FF41}'..='\u{FF5A}'
+                    if self.fullwidth_alnum =>
+                {
+                    output.push(char::from_u32(c as u32 - 0xFEE0).unwrap_or(c))
+                }
+                '\u{200B}' | '\u{200C}' | '\u{2060}' | '\u{FEFF}' if self.zero_width => (),
+                c => output.push(c),
+            }
+        }
+        output.extend(katakana.nfkc());
+        output.into()
+    }
+}
+
+/// Removal of hearing-impaired (SDH) annotations
+#[derive(Debug, Clone, Copy, Store, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SdhRemoval {
+    /// `[door slams]`
+    pub brackets: bool,
+    /// `(LAUGHS)`
+    pub parentheses: bool,
+    /// `♪ lyrics ♪`
+    pub music: bool,
+    /// `JOHN: ...`
+    pub speaker_labels: bool,
+    /// Lines written in upper case entirely
+    pub all_caps: bool,
+}
+
+/// Min number of upper case letters for a line to be taken as an all-caps
+/// SDH annotation
+const MIN_CAPS_LETTERS: usize = 4;
+
+impl SdhRemoval {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.brackets || self.parentheses || self.music || self.speaker_labels || self.all_caps
+    }
+
+    pub(crate) fn remove<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        lazy_static! {
+            static ref RE_BRACKETS: Regex = Regex::new(r"[\[【][^\]】]*[\]】]").unwrap();
+            static ref RE_PARENTHESES: Regex = Regex::new(r"[(（][^)）]*[)）]").unwrap();
+            static ref RE_MUSIC: Regex = Regex::new(r"[♪♫♬♩][^♪♫♬♩\r\n]*[♪♫♬♩]?").unwrap();
+            static ref RE_SPEAKER: Regex =
+                Regex::new(r"(?m)^(\s*-?\s*)[A-Z][A-Z0-9 .'\-]*:\s*").unwrap();
+        }
+        if !self.is_enabled() {
+            return text;
+        }
+        let mut text = text;
+        for (enabled, re, rep) in [
+            (self.brackets, &*RE_BRACKETS, ""),
+            (self.parentheses, &*RE_PARENTHESES, ""),
+            (self.music, &*RE_MUSIC, ""),
+            (self.speaker_labels, &*RE_SPEAKER, "${1}"),
+        ] {
+            if !enabled {
+                continue;
+            }
+            let replaced = match re.replace_all(&text, rep) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(replaced) = replaced {
+                text = replaced.into();
+            }
+        }
+        // at least a few letters, so short shouts like "OK" or "NO!" are kept
+        let is_all_caps = |line: &str| {
+            line.chars()
+                .filter(|c| c.is_uppercase())
+                .nth(MIN_CAPS_LETTERS - 1)
+                .is_some()
+                && !line.chars().any(char::is_lowercase)
+        };
+        let keep =
+            |line: &str| !line.is_empty() && line != "-" && !(self.all_caps && is_all_caps(line));
+        if text.lines().all(|line| line.trim() == line && keep(line)) {
+            return text;
+        }
+        text.lines()
+            .map(str::trim)
+            .filter(|line| keep(line))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+            .into()
+    }
+}
+
 #[derive(Debug, Clone, Store, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
 pub struct Options {
     pub ass_charset: String,
+    /// Comma-separated encodings tried in order when detection fails
+    pub fallback_charsets: String,
+    /// Repair text decoded with wrong codepage & saved again as UTF-8
+    pub mojibake_repair: bool,
     pub srt_charset: String,
+    /// Encode output in the detected input encoding of each file,
+    /// instead of `srt_charset`
+    pub same_charset: bool,
+    pub line_ending: LineEnding,
+    /// Prepend BOM to UTF-8 output (UTF-16 output always has BOM)
+    pub utf8_bom: bool,
     pub chinese_convertion: ChineseConvertion,
+    /// Take script from filename tags (e.g. ".sc.ass") to pick the convertion
+    /// direction, & rewrite them
+    pub filename_tags: bool,
     pub line_strip: LineStrip,
     pub offset_millis: i32,
     pub no_zip: bool,
+    /// Add a list of failed files into the output zip
+    pub zip_errors_file: bool,
+    /// Max number of workers converting in parallel, 0 for the number
+    /// of logical processors. Capped by `converter::MAX_WORKERS` anyway.
+    pub max_workers: u32,
+    /// Max characters per line, 0 for no wrapping
+    pub max_line_chars: u32,
+    /// Max lines per cue, 0 for unlimited
+    pub max_lines: u32,
+    pub sdh_removal: SdhRemoval,
+    /// Applied in order, after line strip
+    pub replace_rules: Vec<ReplaceRule>,
+    pub replace_stage: ReplaceStage,
+    pub drop_rules: Vec<DropRule>,
+    /// Also drop cues matching built-in fansub credit patterns
+    pub drop_credits: bool,
+    pub normalization: TextNormalization,
+    /// Per-file overrides, the first matched rule takes effect
+    pub option_rules: Vec<OptionRule>,
 }
 
 impl Options {
@@ -106,28 +504,93 @@ impl Options {
     }
 }
 
+/// User-supplied conversion dictionary & glossary
+#[derive(Debug, Clone, Store, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct UserDict {
+    /// Tab-separated entries, same format as OpenCC dicts
+    pub entries: String,
+    /// Terms that never be converted, one per line
+    pub glossary: String,
+}
+
+impl UserDict {
+    pub(crate) fn load_from_storage() -> Self {
+        storage::get_from_json(Key::UserDict).unwrap_or_default()
+    }
+
+    pub(crate) fn save_to_storage(&self) -> Result<(), JsValue> {
+        storage::set(
+            Key::UserDict,
+            serde_json::to_string(self).expect("failed to serialize"),
+        )
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.trim().is_empty() && self.glossary.trim().is_empty()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum WorkerMessage {
     WorkerReady,
+    TaskProgress(TaskProgress),
     TaskDone(Result<TaskResult, ConvertError>),
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskProgress {
+    /// Number of ASS files found so far, grows as zip files are opened
+    pub files_discovered: usize,
+    pub files_done: usize,
+    /// File being converted
+    pub current_file: Option<String>,
+    /// Input bytes of done files
+    pub bytes_processed: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskResult {
     filename: String,
     file_url: String,
     meta: ConvertMeta,
+    files: Vec<FileStatus>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskRequest {
     pub options: Options,
+    pub user_dict: UserDict,
     pub files: Vec<FileWrap>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileWrap(#[serde(with = "serde_wasm_bindgen::preserve")] pub File);
 
+#[test]
+fn test_script_from_hits() {
+    assert_eq!(Script::from_hits(0, 0), Script::Unknown);
+    assert_eq!(Script::from_hits(100, 3), Script::Simplified);
+    assert_eq!(Script::from_hits(2, 50), Script::Traditional);
+    assert_eq!(Script::from_hits(40, 60), Script::Mixed);
+}
+
+#[test]
+fn test_chinese_convertion_tagged() {
+    use ChineseConvertion::*;
+    let traditional = Some(Script::Traditional);
+    let simplified = Some(Script::Simplified);
+    assert_eq!(ToTaiwan.dict_chain_from(traditional), ["dict/t2tw.bin"]);
+    assert_eq!(ToTaiwan.dict_chain_from(simplified), ToTaiwan.dict_chain());
+    assert!(AutoToTraditional.dict_chain_from(traditional).is_empty());
+    assert!(HongKongToSimplified.dict_chain_from(simplified).is_empty());
+    assert_eq!(ToShinjitai.dict_chain_from(simplified), ["dict/t2jp.bin"]);
+    assert_eq!(ToHongKong.dict_chain_from(None), ToHongKong.dict_chain());
+    // Keep is disabled, tags don't turn it into a convertion
+    assert!(Keep.dict_chain_from(traditional).is_empty());
+    assert!(Keep.dict_chain_from(simplified).is_empty());
+}
+
 #[test]
 fn test_line_strip() {
     let empty = Cow::Borrowed("");
@@ -151,3 +614,72 @@ fn test_line_strip() {
         Cow::Owned(_)
     ));
 }
+
+#[test]
+fn test_sdh_removal() {
+    let all = SdhRemoval {
+        brackets: true,
+        parentheses: true,
+        music: true,
+        speaker_labels: true,
+        all_caps: true,
+    };
+    let remove = |sdh: &SdhRemoval, text: &'static str| sdh.remove(Cow::Borrowed(text));
+
+    assert_eq!(
+        remove(&SdhRemoval::default(), "[door slams]"),
+        "[door slams]"
+    );
+    assert_eq!(remove(&all, "[door slams]"), "");
+    assert_eq!(remove(&all, "(LAUGHS) Really?"), "Really?");
+    assert_eq!(remove(&all, "♪ la la la ♪"), "");
+    assert_eq!(remove(&all, "♪ unclosed lyrics\r\nHi"), "Hi");
+    assert_eq!(
+        remove(&all, "JOHN: Hello.\r\n- MARY: Hi."),
+        "Hello.\r\n- Hi."
+    );
+    assert_eq!(remove(&all, "DOOR SLAMS\r\nWho's there?"), "Who's there?");
+    assert_eq!(remove(&all, "【字幕】你好（笑）"), "你好");
+
+    let caps_only = SdhRemoval {
+        all_caps: true,
+        ..Default::default()
+    };
+    assert_eq!(remove(&caps_only, "HELLO\r\nOK"), "OK");
+    assert!(matches!(
+        remove(&caps_only, "I\r\nOK\r\nNO!"),
+        Cow::Borrowed("I\r\nOK\r\nNO!")
+    ));
+    assert!(matches!(remove(&all, "Hello."), Cow::Borrowed("Hello.")));
+}
+
+#[test]
+fn test_text_normalization() {
+    let normalize = |norm: TextNormalization, text: &'static str| norm.normalize(text.into());
+    let folding = TextNormalization {
+        fullwidth_alnum: true,
+        halfwidth_katakana: true,
+        zero_width: true,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        normalize(Default::default(), "ＡＢＣ"),
+        Cow::Borrowed("ＡＢＣ")
+    ));
+    assert_eq!(normalize(folding, "ＡＢＣ１２３，ｘ"), "ABC123，x");
+    assert_eq!(normalize(folding, "ｶﾞｷﾞｸﾞ ﾊﾟﾝ"), "ガギグ パン");
+    assert_eq!(normalize(folding, "a\u{200B}b\u{FEFF}"), "ab");
+
+    let nfc = TextNormalization {
+        form: NormalForm::Nfc,
+        ..Default::default()
+    };
+    assert_eq!(normalize(nfc, "e\u{301}"), "\u{E9}");
+    assert_eq!(normalize(nfc, "\u{F900}"), "\u{8C48}");
+    let nfkc = TextNormalization {
+        form: NormalForm::Nfkc,
+        ..Default::default()
+    };
+    assert_eq!(normalize(nfkc, "ＡＢ①"), "AB1");
+}
@@ -0,0 +1,281 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{BIG5, EUC_KR, Encoding, GBK, SHIFT_JIS};
+use serde::{Deserialize, Serialize};
+
+use super::{ConvertError, subtitle};
+
+/// Charset implied by ASS header, used as the TLD hint of chardetng
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CharsetHint {
+    tld: &'static [u8],
+    pub(crate) encoding: &'static Encoding,
+}
+
+impl CharsetHint {
+    const CN: Self = Self {
+        tld: b"cn",
+        encoding: GBK,
+    };
+    const TW: Self = Self {
+        tld: b"tw",
+        encoding: BIG5,
+    };
+    const JP: Self = Self {
+        tld: b"jp",
+        encoding: SHIFT_JIS,
+    };
+    const KR: Self = Self {
+        tld: b"kr",
+        encoding: EUC_KR,
+    };
+
+    /// From the Windows charset number in the `Encoding` column of styles
+    fn from_style_encoding(value: &[u8]) -> Option<Self> {
+        match value {
+            b"134" => Some(Self::CN),
+            b"136" => Some(Self::TW),
+            b"128" => Some(Self::JP),
+            b"129" => Some(Self::KR),
+            _ => None,
+        }
+    }
+
+    /// From the `Language` field of [Script Info] (libass extension)
+    fn from_language(value: &[u8]) -> Option<Self> {
+        let value = value.to_ascii_lowercase();
+        if ["zh-hant", "zh-tw", "zh-hk", "zh-mo"]
+            .iter()
+            .any(|lang| value.starts_with(lang.as_bytes()))
+        {
+            Some(Self::TW)
+        } else if value.starts_with(b"zh") {
+            Some(Self::CN)
+        } else if value.starts_with(b"ja") {
+            Some(Self::JP)
+        } else if value.starts_with(b"ko") {
+            Some(Self::KR)
+        } else {
+            None
+        }
+    }
+
+    /// Parse ASS header without decoding, as it's ASCII-compatible
+    pub(crate) fn find(input: &[u8]) -> Option<Self> {
+        let mut section: &[u8] = b"";
+        let mut encoding_col = None;
+        let mut language = None;
+        let mut styles = Vec::new();
+        for line in input.split(|b| *b == b'\n').map(<[u8]>::trim_ascii) {
+            if line.starts_with(b"[") {
+                section = line;
+                if section.eq_ignore_ascii_case(b"[Events]") {
+                    break;
+                }
+                continue;
+            }
+            let Some((key, value)) = split_once(line, b':') else {
+                continue;
+            };
+            let value = value.trim_ascii();
+            match (section, key) {
+                (b"[Script Info]", b"Language") => {
+                    language = CharsetHint::from_language(value);
+                }
+                (b"[V4+ Styles]" | b"[V4 Styles]", b"Format") => {
+                    encoding_col = value
+                        .split(|b| *b == b',')
+                        .position(|col| col.trim_ascii() == b"Encoding");
+                }
+                (b"[V4+ Styles]" | b"[V4 Styles]", b"Style") => {
+                    let hint = encoding_col
+                        .and_then(|n| value.split(|b| *b == b',').nth(n))
+                        .and_then(|v| Self::from_style_encoding(v.trim_ascii()));
+                    styles.extend(hint);
+                }
+                _ => (),
+            }
+        }
+        // take the most common one among styles
+        let style_hint = styles
+            .iter()
+            .max_by_key(|hint| styles.iter().filter(|h| h == hint).count())
+            .copied();
+        style_hint.or(language)
+    }
+}
+
+fn split_once(line: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let n = line.iter().position(|b| *b == sep)?;
+    Some((&line[..n], &line[n + 1..]))
+}
+
+/// TLDs to vote for encoding candidates with, besides the hint
+const CANDIDATE_TLDS: &[&[u8]] = &[
+    b"cn", b"tw", b"hk", b"jp", b"kr", b"ru", b"gr", b"tr", b"vn", b"th",
+];
+/// Max number of candidates kept
+const MAX_CANDIDATES: usize = 3;
+
+/// Possible encoding of input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Candidate {
+    pub(crate) encoding: String,
+    /// True if the detector is sure about it under any of the TLDs
+    pub(crate) sure: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Detection {
+    /// None if the detector is unsure & no usable hint
+    pub(crate) encoding: Option<&'static Encoding>,
+    /// True if the detector itself is sure about `encoding`
+    pub(crate) sure: bool,
+    /// True if the hint is taken as the result since the detector is unsure
+    pub(crate) hint_fallback: bool,
+    pub(crate) candidates: Vec<Candidate>,
+}
+
+/// Let the detector guess under each TLD, sure guesses count twice.
+/// Encodings that cannot decode input without error are excluded.
+fn vote_candidates(
+    detector: &EncodingDetector,
+    input: &[u8],
+    hint: Option<CharsetHint>,
+) -> Vec<Candidate> {
+    let tlds = hint
+        .map(|h| h.tld)
+        .into_iter()
+        .chain(CANDIDATE_TLDS.iter().copied())
+        .map(Some)
+        .chain([None]);
+    let mut votes: Vec<(&'static Encoding, usize, bool)> = Vec::new();
+    for tld in tlds {
+        let (encoding, sure) = detector.guess_assess(tld, true);
+        let weight = if sure { 2 } else { 1 };
+        match votes.iter_mut().find(|(e, _, _)| *e == encoding) {
+            Some((_, n, any_sure)) => {
+                *n += weight;
+                *any_sure |= sure;
+            }
+            None => votes.push((encoding, weight, sure)),
+        }
+    }
+    votes.retain(|(encoding, _, _)| {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(input)
+            .is_some()
+    });
+    votes.sort_by_key(|(_, n, _)| std::cmp::Reverse(*n));
+    votes
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|(encoding, _, sure)| Candidate {
+            encoding: encoding.name().to_string(),
+            sure,
+        })
+        .collect()
+}
+
+/// Guess encoding of input, take the hint as fallback when the detector
+/// is unsure.
+pub(crate) fn detect_encoding(input: &[u8], hint: Option<CharsetHint>) -> Detection {
+    let tld = hint.map(|h| h.tld);
+    let mut detector = EncodingDetector::new();
+    let mut sure_encoding = None;
+    for chunk in input.chunks(256) {
+        if detector.feed(chunk, false) {
+            let (encoding, sure) = detector.guess_assess(tld, true);
+            if sure {
+                sure_encoding = Some(encoding);
+                break;
+            }
+        }
+    }
+    if sure_encoding.is_none() {
+        detector.feed(&[], true);
+        let (encoding, sure) = detector.guess_assess(tld, true);
+        sure_encoding = Some(encoding).take_if(|_| sure);
+    }
+    let candidates = vote_candidates(&detector, input, hint);
+    if sure_encoding.is_some() {
+        return Detection {
+            encoding: sure_encoding,
+            sure: true,
+            hint_fallback: false,
+            candidates,
+        };
+    }
+    let encoding = hint.map(|h| h.encoding).filter(|encoding| {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(input)
+            .is_some()
+    });
+    Detection {
+        encoding,
+        sure: false,
+        hint_fallback: encoding.is_some(),
+        candidates,
+    }
+}
+
+/// Parse comma-separated encoding labels
+pub(crate) fn parse_labels(labels: &str) -> Result<Vec<&'static Encoding>, ConvertError> {
+    labels
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| ConvertError::EncodingLabel(label.to_string()))
+        })
+        .collect()
+}
+
+/// Find the first encoding which decodes input without error and
+/// results a parseable [Events] section
+pub(crate) fn try_fallbacks(
+    input: &[u8],
+    fallbacks: &[&'static Encoding],
+) -> Option<&'static Encoding> {
+    fallbacks.iter().copied().find(|encoding| {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(input)
+            .is_some_and(|ass| subtitle::has_events(&ass))
+    })
+}
+
+#[test]
+fn test_find_charset_hint() {
+    let ass = b"[Script Info]\r\nLanguage: ja\r\n\r\n\
+        [V4+ Styles]\r\n\
+        Format: Name, Fontname, Fontsize, Encoding\r\n\
+        Style: Default,\xba\xda\xcc\xe5,20,134\r\n\
+        Style: Alt,Arial,20,1\r\n\
+        Style: Sign,Arial,20,134\r\n\r\n\
+        [Events]\r\nStyle: Fake,Arial,20,136\r\n";
+    assert_eq!(CharsetHint::find(ass), Some(CharsetHint::CN));
+
+    let ass =
+        b"[Script Info]\nLanguage: zh-Hant\n[V4+ Styles]\nFormat: Name, Encoding\nStyle: A,1\n";
+    assert_eq!(CharsetHint::find(ass), Some(CharsetHint::TW));
+
+    assert_eq!(CharsetHint::find(b"[Events]\nFormat: Text\n"), None);
+}
+
+#[test]
+fn test_parse_labels() {
+    let encodings = parse_labels(" GB18030, big5,,Shift_JIS ").unwrap();
+    assert_eq!(encodings, [encoding_rs::GB18030, BIG5, SHIFT_JIS]);
+    assert!(parse_labels("").unwrap().is_empty());
+    assert!(parse_labels("GBK, nope").is_err());
+}
+
+#[test]
+fn test_try_fallbacks() {
+    // "字幕" in GBK, invalid in UTF-8
+    let input =
+        b"[Events]\nFormat: Start, End, Text\nDialogue: 0:00:00.00,0:00:01.00,\xd7\xd6\xc4\xbb\n";
+    let fallbacks = [encoding_rs::UTF_8, GBK, SHIFT_JIS];
+    assert_eq!(try_fallbacks(input, &fallbacks), Some(GBK));
+    assert_eq!(try_fallbacks(b"\xd7\xd6", &fallbacks), None);
+}
@@ -0,0 +1,76 @@
+use regex_lite::Regex;
+
+use super::ConvertError;
+use crate::{DropField, DropRule};
+
+/// Common credit, staff & ads lines of fansub groups
+const CREDIT_PATTERNS: &[&str] = &[
+    r"本字幕(由|仅供|僅供)",
+    r"(字幕[组組社]|[Ss]ubs?)\S*(出品|制作|製作|作品|荣誉|榮譽)",
+    r"^(翻译|翻譯|校对|校對|时轴|時軸|压制|壓制|后期|後期|特效|片源|监制|監製|总监|總監)\s*[:：]",
+    r"(QQ|qq|微信|微博|[Tt]elegram|TG)\s*群?\s*[:：号號]",
+    r"(仅供|僅供)(学习|學習|交流|试看|試看)",
+    r"禁止(用于|用於)?(商业|商業)",
+    r"(招募|诚招|誠招)(翻译|翻譯|校对|校對|时轴|時軸|成员|成員)",
+];
+
+/// Compiled list of [DropRule]
+#[derive(Debug, Default)]
+pub(crate) struct CueFilter {
+    rules: Vec<(DropField, Regex)>,
+}
+
+impl CueFilter {
+    /// Compile rules, rules with empty pattern are ignored
+    pub(crate) fn new(rules: &[DropRule], credits: bool) -> Result<Self, ConvertError> {
+        let credits = CREDIT_PATTERNS
+            .iter()
+            .filter(|_| credits)
+            .map(|pattern| (DropField::Text, Regex::new(pattern).unwrap()));
+        let rules = rules
+            .iter()
+            .filter(|rule| !rule.pattern.is_empty())
+            .map(|rule| match rule.compile() {
+                Ok(re) => Ok((rule.field, re)),
+                Err(err) => Err(ConvertError::Regex {
+                    pattern: rule.pattern.clone(),
+                    msg: err.to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            rules: credits.chain(rules).collect(),
+        })
+    }
+
+    /// Return true if the cue should be dropped
+    pub(crate) fn matches(&self, style: &str, name: &str, text: &str) -> bool {
+        self.rules.iter().any(|(field, re)| match field {
+            DropField::Text => re.is_match(text),
+            DropField::Style => re.is_match(style),
+            DropField::Name => re.is_match(name),
+        })
+    }
+}
+
+#[test]
+fn test_cue_filter() {
+    let rule = |field: DropField, pattern: &str| DropRule {
+        field,
+        pattern: pattern.to_string(),
+    };
+    let filter = CueFilter::new(
+        &[rule(DropField::Style, "^staff$"), rule(DropField::Name, "")],
+        false,
+    )
+    .unwrap();
+    assert!(filter.matches("staff", "", "text"));
+    assert!(!filter.matches("Default", "", "本字幕由XX字幕组制作"));
+
+    let filter = CueFilter::new(&[], true).unwrap();
+    assert!(filter.matches("Default", "", "本字幕由XX字幕组制作"));
+    assert!(filter.matches("Default", "", "XX字幕组出品"));
+    assert!(filter.matches("Default", "", "QQ群: 12345"));
+    assert!(filter.matches("Default", "", "翻译：某某"));
+    assert!(!filter.matches("Default", "", "我们走吧"));
+}
@@ -0,0 +1,254 @@
+use gloo_net::http::Request;
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Cache, Response, WorkerGlobalScope};
+
+use super::{create_blob, dict_bin::BinDict, user_dict::UserConverter};
+use crate::{ChineseConvertion, Script, UserDict};
+
+#[cfg(feature = "embed-dict")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/embedded_dict.rs"));
+}
+
+/// Browser cache for compiled dicts, named after the hash of dict sources
+/// (see build.rs) so it's invalidated whenever the dicts change
+const CACHE_NAME: &str = concat!("asstosrt-dict-", env!("DICT_HASH"));
+
+type DictChain = Rc<[Rc<BinDict>]>;
+
+thread_local! {
+    /// Loaded dicts keyed by dict name, shared among chains
+    static DICT_CACHE: RefCell<HashMap<&'static str, Rc<BinDict>>> = RefCell::default();
+}
+
+async fn open_cache() -> Result<Cache, JsValue> {
+    let scope: WorkerGlobalScope = js_sys::global().dyn_into()?;
+    JsFuture::from(scope.caches()?.open(CACHE_NAME))
+        .await?
+        .dyn_into()
+}
+
+async fn get_cached_bytes(cache: &Cache, name: &str) -> Result<Option<Vec<u8>>, JsValue> {
+    let resp = JsFuture::from(cache.match_with_str(name)).await?;
+    if resp.is_undefined() {
+        return Ok(None);
+    }
+    let resp: Response = resp.dyn_into()?;
+    let buf = JsFuture::from(resp.array_buffer()?).await?;
+    Ok(Some(js_sys::Uint8Array::new(&buf).to_vec()))
+}
+
+async fn put_cached_bytes(cache: &Cache, name: &str, bytes: &[u8]) -> Result<(), JsValue> {
+    let blob = create_blob(bytes, "application/octet-stream")?;
+    let resp = Response::new_with_opt_blob(Some(&blob))?;
+    JsFuture::from(cache.put_with_str(name, &resp)).await?;
+    Ok(())
+}
+
+fn load_bin_dict(name: &str, bytes: Vec<u8>) -> Result<BinDict, gloo_net::Error> {
+    BinDict::load(bytes.into())
+        .map_err(|err| gloo_net::Error::GlooError(format!("{} ({})", err, name)))
+}
+
+/// Fetch compiled dict from browser cache or network
+async fn fetch_opencc_dict(name: &str) -> Result<BinDict, gloo_net::Error> {
+    // Cache API may be unavailable (e.g. insecure context), ignore its errors
+    let cache = match open_cache().await {
+        Ok(cache) => Some(cache),
+        Err(err) => {
+            log::debug!("browser cache unavailable: {:?}", err);
+            None
+        }
+    };
+    if let Some(cache) = &cache {
+        match get_cached_bytes(cache, name).await {
+            Ok(Some(bytes)) => {
+                log::debug!("dict {} hit browser cache", name);
+                return load_bin_dict(name, bytes);
+            }
+            Ok(None) => log::debug!("dict {} miss browser cache", name),
+            Err(err) => log::debug!("failed to read browser cache: {:?}", err),
+        }
+    }
+
+    let resp = Request::get(name).send().await?;
+    if !resp.ok() {
+        return Err(gloo_net::Error::GlooError(format!(
+            "{} {} ({})",
+            resp.status(),
+            resp.status_text(),
+            name
+        )));
+    }
+    let bytes = resp.binary().await?;
+    let dict = load_bin_dict(name, bytes.clone())?;
+    if let Some(cache) = &cache
+        && let Err(err) = put_cached_bytes(cache, name, &bytes).await
+    {
+        log::warn!("failed to put dict into browser cache: {:?}", err);
+    }
+    Ok(dict)
+}
+
+/// Load dict from the embedded one if any, fetch it otherwise
+async fn load_opencc_dict(name: &str) -> Result<BinDict, gloo_net::Error> {
+    #[cfg(feature = "embed-dict")]
+    if let Some(deflated) = embedded::embedded_dict(name) {
+        log::debug!("load embedded dict {}", name);
+        let bytes =
+            miniz_oxide::inflate::decompress_to_vec(deflated).expect("corrupted embedded dict");
+        return load_bin_dict(name, bytes);
+    }
+    fetch_opencc_dict(name).await
+}
+
+/// Load dict, or get it from cache
+async fn load_dict(name: &'static str) -> Result<Rc<BinDict>, gloo_net::Error> {
+    if let Some(dict) = DICT_CACHE.with_borrow(|cache| cache.get(name).cloned()) {
+        log::debug!("dict {} hit cache", name);
+        return Ok(dict);
+    }
+    log::debug!("dict {} miss cache", name);
+    let dict = Rc::new(load_opencc_dict(name).await?);
+    DICT_CACHE.with_borrow_mut(|cache| cache.insert(name, dict.clone()));
+    Ok(dict)
+}
+
+/// Load dicts in chain, each one is cached individually
+async fn load_dict_chain(chain: &'static [&'static str]) -> Result<DictChain, gloo_net::Error> {
+    let mut dicts = Vec::with_capacity(chain.len());
+    for name in chain {
+        dicts.push(load_dict(name).await?);
+    }
+    Ok(dicts.into())
+}
+
+/// Tell Simplified & Traditional Chinese apart by hits on s2t/t2s dicts
+struct ScriptDetector {
+    s2t: DictChain,
+    t2s: DictChain,
+}
+
+impl ScriptDetector {
+    async fn load() -> Result<Self, gloo_net::Error> {
+        Ok(Self {
+            s2t: load_dict_chain(ChineseConvertion::ToTraditional.dict_chain()).await?,
+            t2s: load_dict_chain(ChineseConvertion::ToSimplified.dict_chain()).await?,
+        })
+    }
+
+    fn detect(&self, text: &str) -> Script {
+        let (mut simplified, mut traditional) = (0, 0);
+        let mut buf = [0u8; 4];
+        for c in text.chars().filter(|c| !c.is_ascii()) {
+            let c = c.encode_utf8(&mut buf);
+            // characters exist in both dicts are ambiguous
+            match (
+                self.s2t.iter().any(|d| d.contains(c)),
+                self.t2s.iter().any(|d| d.contains(c)),
+            ) {
+                (true, false) => simplified += 1,
+                (false, true) => traditional += 1,
+                _ => (),
+            }
+        }
+        Script::from_hits(simplified, traditional)
+    }
+}
+
+/// Chain of OpenCC dicts, with user dict ahead of them
+#[derive(Default)]
+pub(crate) struct ChineseConverter {
+    dicts: DictChain,
+    user: UserConverter,
+    detector: Option<ScriptDetector>,
+    target: Option<Script>,
+    auto: bool,
+}
+
+impl ChineseConverter {
+    /// `source` is the script claimed by filename tag, if any
+    pub(crate) async fn load(
+        convertion: ChineseConvertion,
+        source: Option<Script>,
+        user_dict: &UserDict,
+    ) -> Result<Self, gloo_net::Error> {
+        // detection is only needed to skip untagged files in auto mode, save
+        // loading both s2t & t2s dicts for the others
+        let auto = convertion.is_auto();
+        let detector = if auto && source.is_none() {
+            Some(ScriptDetector::load().await?)
+        } else {
+            None
+        };
+        Ok(Self {
+            dicts: load_dict_chain(convertion.dict_chain_from(source)).await?,
+            user: UserConverter::new(user_dict),
+            detector,
+            target: convertion.target_script(),
+            auto,
+        })
+    }
+
+    /// Detect script of the text, None if not an auto convertion
+    pub(crate) fn detect(&self, text: &str) -> Option<Script> {
+        self.detector.as_ref().map(|d| d.detect(text))
+    }
+
+    /// Script of the converted text, None if not a Chinese convertion
+    pub(crate) fn target(&self) -> Option<Script> {
+        self.target
+    }
+
+    /// Whether text in `script` need to be converted
+    pub(crate) fn should_convert(&self, script: Option<Script>) -> bool {
+        !self.dicts.is_empty() && !(self.auto && script.is_some() && script == self.target)
+    }
+
+    /// Convert text, `on_replace` is called with (from, to) on each
+    /// replacement made by OpenCC dicts
+    pub(crate) fn convert<'a>(
+        &self,
+        text: Cow<'a, str>,
+        mut on_replace: impl FnMut(&str, &str),
+    ) -> Cow<'a, str> {
+        if self.dicts.is_empty() {
+            return text;
+        }
+        let mut text = self.user.mask(text);
+        for dict in self.dicts.iter() {
+            text = Cow::Owned(dict.replace_all_with(&text, &mut on_replace));
+        }
+        self.user.unmask(text)
+    }
+}
+
+/// Converters loaded on demand, as option rules may override the convertion
+pub(crate) struct ChineseConverters<'a> {
+    user_dict: &'a UserDict,
+    loaded: HashMap<(ChineseConvertion, Option<Script>), ChineseConverter>,
+}
+
+impl<'a> ChineseConverters<'a> {
+    pub(crate) fn new(user_dict: &'a UserDict) -> Self {
+        Self {
+            user_dict,
+            loaded: HashMap::new(),
+        }
+    }
+
+    pub(crate) async fn get(
+        &mut self,
+        convertion: ChineseConvertion,
+        source: Option<Script>,
+    ) -> Result<&ChineseConverter, gloo_net::Error> {
+        let key = (convertion, source);
+        if !self.loaded.contains_key(&key) {
+            let converter = ChineseConverter::load(convertion, source, self.user_dict).await?;
+            self.loaded.insert(key, converter);
+        }
+        Ok(&self.loaded[&key])
+    }
+}
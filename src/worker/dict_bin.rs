@@ -0,0 +1,192 @@
+//! Precompiled dict format, created by `dict_compile.rs` (run by
+//! `scripts/compile_dict`, or by `build.rs` for embedded dicts).
+//!
+//! All integers are little-endian u32:
+//!
+//! ```text
+//! magic "SCD\x01" | max key length in chars | number of entries (n)
+//! key offsets [n + 1] | value offsets [n + 1] | keys | values
+//! ```
+//!
+//! Keys are sorted by their UTF-8 bytes, so we can look up them with binary
+//! search directly on the buffer without any parsing.
+
+#[cfg(test)]
+use super::dict_compile::compile_dict;
+
+const MAGIC: &[u8; 4] = b"SCD\x01";
+const HEADER_LEN: usize = 12;
+
+pub(crate) struct BinDict {
+    data: Box<[u8]>,
+    len: usize,
+    max_chars: usize,
+    keys_at: usize,
+    values_at: usize,
+}
+
+impl BinDict {
+    pub(crate) fn load(data: Box<[u8]>) -> Result<Self, String> {
+        let read = |pos: usize| {
+            data.get(pos..pos + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+                .ok_or_else(|| "unexpected end of dict".to_string())
+        };
+        if data.get(..4) != Some(MAGIC) {
+            return Err("not a compiled dict".into());
+        }
+        let max_chars = read(4)?;
+        let len = read(8)?;
+        let keys_at = HEADER_LEN + (len + 1) * 8;
+        let values_at = keys_at + read(HEADER_LEN + len * 4)?;
+        // validate once here, so lookups can skip bound & utf-8 checks
+        if values_at + read(HEADER_LEN + (len * 2 + 1) * 4)? != data.len() {
+            return Err("corrupted dict size".into());
+        }
+        for table in [HEADER_LEN, HEADER_LEN + (len + 1) * 4] {
+            for i in 0..len {
+                if read(table + i * 4)? > read(table + (i + 1) * 4)? {
+                    return Err("corrupted dict offsets".into());
+                }
+            }
+        }
+        std::str::from_utf8(&data[keys_at..]).map_err(|err| err.to_string())?;
+        let dict = Self {
+            len,
+            max_chars,
+            keys_at,
+            values_at,
+            data,
+        };
+        if (1..len).any(|i| dict.key(i - 1) >= dict.key(i)) {
+            return Err("dict keys not sorted".into());
+        }
+        Ok(dict)
+    }
+
+    fn read_u32(&self, pos: usize) -> usize {
+        u32::from_le_bytes(self.data[pos..pos + 4].try_into().unwrap()) as usize
+    }
+
+    fn key(&self, idx: usize) -> &[u8] {
+        let start = self.read_u32(HEADER_LEN + idx * 4);
+        let end = self.read_u32(HEADER_LEN + (idx + 1) * 4);
+        &self.data[self.keys_at + start..self.keys_at + end]
+    }
+
+    fn value(&self, idx: usize) -> &[u8] {
+        let table = HEADER_LEN + (self.len + 1) * 4;
+        let start = self.read_u32(table + idx * 4);
+        let end = self.read_u32(table + (idx + 1) * 4);
+        &self.data[self.values_at + start..self.values_at + end]
+    }
+
+    fn find(&self, key: &[u8]) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.key(mid).cmp(key) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.find(key.as_bytes()).is_some()
+    }
+
+    /// Replace text with the longest matched key at each position,
+    /// same as what [simplecc::Dict::replace_all] does.
+    pub(crate) fn replace_all(&self, text: &str) -> String {
+        self.replace_all_with(text, |_, _| ())
+    }
+
+    /// Like [Self::replace_all], and call `on_replace` with (key, value) on
+    /// each replacement that changes the text.
+    pub(crate) fn replace_all_with(
+        &self,
+        text: &str,
+        mut on_replace: impl FnMut(&str, &str),
+    ) -> String {
+        let offsets: Vec<_> = text
+            .char_indices()
+            .map(|(n, _)| n)
+            .chain([text.len()])
+            .collect();
+        let mut output = String::with_capacity(text.len());
+        let mut i = 0;
+        while i + 1 < offsets.len() {
+            let found = (1..=self.max_chars.min(offsets.len() - 1 - i))
+                .rev()
+                .find_map(|n| {
+                    let key = &text[offsets[i]..offsets[i + n]];
+                    self.find(key.as_bytes()).map(|idx| (key, n, idx))
+                });
+            match found {
+                Some((key, n, idx)) => {
+                    let value = String::from_utf8_lossy(self.value(idx));
+                    if key != value {
+                        on_replace(key, &value);
+                    }
+                    output.push_str(&value);
+                    i += n;
+                }
+                None => {
+                    output.push_str(&text[offsets[i]..offsets[i + 1]]);
+                    i += 1;
+                }
+            }
+        }
+        output
+    }
+}
+
+#[test]
+fn test_bin_dict() {
+    let dict = BinDict::load(compile_dict("干\t乾 干\n干燥\t乾燥\n后\t後\n").into()).unwrap();
+    assert_eq!(dict.replace_all("干燥后干a"), "乾燥後乾a");
+    assert_eq!(dict.replace_all(""), "");
+    let mut replaced = Vec::new();
+    dict.replace_all_with("干燥a后", |k, v| {
+        replaced.push((k.to_string(), v.to_string()))
+    });
+    assert_eq!(
+        replaced,
+        [("干燥".into(), "乾燥".into()), ("后".into(), "後".into())]
+    );
+
+    let empty = BinDict::load(compile_dict("").into()).unwrap();
+    assert_eq!(empty.replace_all("abc"), "abc");
+
+    assert!(BinDict::load(Box::new([])).is_err());
+    let mut corrupted = compile_dict("a\tb\n");
+    corrupted.pop();
+    assert!(BinDict::load(corrupted.into()).is_err());
+}
+
+#[test]
+fn test_bin_dict_same_as_simplecc() {
+    let samples = [
+        "头发发展，后来皇后出了面。",
+        "我們在這裡討論軟體與硬體，還有乾燥的天氣。",
+        "自然語言處理和人工智能 AI 1234",
+    ];
+    for name in ["s2t.txt", "t2s.txt"] {
+        let path = format!("{}/public/dict/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let text = std::fs::read_to_string(path).unwrap();
+        let dict = BinDict::load(compile_dict(&text).into()).unwrap();
+        let reference = simplecc::Dict::load_str(&text);
+        // also convert the keys themselves
+        let keys: String = text
+            .lines()
+            .step_by(37)
+            .filter_map(|l| l.split('\t').next())
+            .collect();
+        for sample in samples.iter().copied().chain([keys.as_str()]) {
+            assert_eq!(dict.replace_all(sample), reference.replace_all(sample));
+        }
+    }
+}
@@ -0,0 +1,35 @@
+// Included by path from `build.rs` & `scripts/compile_dict` as well, keep it
+// free of crate-relative imports & inner attributes.
+
+/// Compile OpenCC dict source into the format read by `BinDict`, see
+/// `dict_bin.rs`. Only the first candidate of each entry is kept.
+pub(crate) fn compile_dict(text: &str) -> Vec<u8> {
+    use std::collections::BTreeMap;
+
+    // later entry overrides the former one
+    let entries: BTreeMap<&str, &str> = text
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, values)| (key, values.split(' ').next().unwrap_or_default()))
+        .collect();
+    let (keys, values): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+    let max_chars = keys.iter().map(|key| key.chars().count()).max();
+
+    let mut output = b"SCD\x01".to_vec();
+    for n in [max_chars.unwrap_or(0), keys.len()] {
+        output.extend((n as u32).to_le_bytes());
+    }
+    for items in [&keys, &values] {
+        let mut offset = 0u32;
+        output.extend(offset.to_le_bytes());
+        for item in items {
+            offset += item.len() as u32;
+            output.extend(offset.to_le_bytes());
+        }
+    }
+    for item in keys.iter().chain(&values) {
+        output.extend(item.as_bytes());
+    }
+    output
+}
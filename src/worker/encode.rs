@@ -0,0 +1,58 @@
+use crate::LineEnding;
+
+pub(crate) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Convert all line endings of `text` into `line_ending`
+pub(crate) fn set_line_ending(text: String, line_ending: LineEnding) -> String {
+    match line_ending {
+        LineEnding::Lf if text.contains('\r') => text.replace("\r\n", "\n"),
+        LineEnding::Crlf if has_bare_lf(&text) => text.replace("\r\n", "\n").replace('\n', "\r\n"),
+        LineEnding::Lf | LineEnding::Crlf => text,
+    }
+}
+
+fn has_bare_lf(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    text.match_indices('\n')
+        .any(|(n, _)| n == 0 || bytes[n - 1] != b'\r')
+}
+
+/// Encode text into UTF-16 with BOM.
+/// It's needed since encoding_rs has no UTF-16 encoder.
+pub(crate) fn encode_utf16(text: &str, big_endian: bool) -> Vec<u8> {
+    let mut output = Vec::with_capacity(text.len() * 2 + 2);
+    let mut push = |unit: u16| {
+        output.extend(if big_endian {
+            unit.to_be_bytes()
+        } else {
+            unit.to_le_bytes()
+        })
+    };
+    push(0xFEFF);
+    let mut buf = [0u16; 2];
+    for c in text.chars() {
+        c.encode_utf16(&mut buf).iter().copied().for_each(&mut push);
+    }
+    output
+}
+
+#[test]
+fn test_set_line_ending() {
+    let lf = || "1\n2\r\n".to_string();
+    assert_eq!(set_line_ending(lf(), LineEnding::Crlf), "1\r\n2\r\n");
+    assert_eq!(set_line_ending(lf(), LineEnding::Lf), "1\n2\n");
+    assert_eq!(set_line_ending("\r\n".into(), LineEnding::Crlf), "\r\n");
+}
+
+#[test]
+fn test_encode_utf16() {
+    assert_eq!(encode_utf16("", false), [0xFF, 0xFE]);
+    assert_eq!(
+        encode_utf16("a\r\n字", false),
+        [0xFF, 0xFE, b'a', 0, b'\r', 0, b'\n', 0, 0x57, 0x5B]
+    );
+    assert_eq!(
+        encode_utf16("\n😀", true),
+        [0xFE, 0xFF, 0, b'\n', 0xD8, 0x3D, 0xDE, 0x00]
+    );
+}
@@ -1,13 +1,30 @@
+mod charset;
+mod cue_filter;
+mod dict;
+mod dict_bin;
+#[cfg(test)]
+mod dict_compile;
+mod encode;
+mod mojibake;
+mod name_tag;
+mod option_rule;
+mod replace;
+mod report;
 mod subtitle;
+mod user_dict;
 mod walk;
 
-use chardetng::EncodingDetector;
+use charset::{Candidate, CharsetHint};
+use cue_filter::CueFilter;
+use dict::{ChineseConverter, ChineseConverters};
+use encode::UTF8_BOM;
 use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
 use futures::channel::oneshot::Canceled;
-use gloo_net::http::Request;
 use js_sys::{Array, Date, Uint8Array};
+use name_tag::NameTag;
+use replace::TextReplacer;
+use report::{FileReport, ReportBuilder};
 use serde::{Deserialize, Serialize};
-use simplecc::Dict;
 use std::{
     borrow::Cow,
     collections::HashSet,
@@ -17,19 +34,23 @@ use std::{
 use thiserror::Error;
 use walk::{FileWalk, ReadToVec};
 use wasm_bindgen::prelude::*;
-use web_sys::{Blob, BlobPropertyBag, File, FileReaderSync, Url};
+use web_sys::{Blob, BlobPropertyBag, FileReaderSync, Url};
 use zip::{
     CompressionMethod,
     result::ZipError,
     write::{SimpleFileOptions, ZipWriter},
 };
 
-use crate::{FileWrap, Options, TaskRequest, TaskResult};
+use crate::{LineEnding, Options, ReplaceStage, Script, TaskProgress, TaskRequest, TaskResult};
+pub(crate) use report::Substitution;
 pub(crate) use subtitle::FormatError;
+use subtitle::{LineWrap, Srt};
 
 pub(crate) const FILE_SIZE_LIMIT: usize = 100 * 1024 * 1024;
 const MIME_SRT: &str = "text/srt";
 const MIME_ZIP: &str = "application/zip";
+/// Listing failed files inside the output zip
+const ERRORS_FILENAME: &str = "errors.txt";
 
 #[derive(Error, Debug, Clone, Serialize, Deserialize)]
 pub enum ConvertError {
@@ -41,16 +62,18 @@ pub enum ConvertError {
     FetchDict(String),
     #[error("unknown encoding label `{0}`")]
     EncodingLabel(String),
-    #[error("utf-16 as output is not supported")]
-    Utf16Output,
     #[error("failed to guess input encoding")]
     EncodingDetect,
+    #[error("invalid regex `{pattern}`: {msg}")]
+    Regex { pattern: String, msg: String },
     #[error("ass format error: {0}")]
     Format(#[from] FormatError),
     #[error("canceled")]
     Canceled,
     #[error("zip file error: {0}")]
     Zip(String),
+    #[error("all {} files failed", .0.len())]
+    AllFailed(Vec<FileStatus>),
     #[error("{name}: {msg}")]
     JsError { name: String, msg: String },
 }
@@ -103,6 +126,108 @@ pub(crate) struct ConvertMeta {
     pub(crate) output_encoding: HashSet<String>,
     pub(crate) decode_error: bool,
     pub(crate) encode_error: bool,
+    /// Number of cues dropped by drop rules
+    pub(crate) dropped_cues: usize,
+    /// Detected Chinese script of each file
+    pub(crate) scripts: Vec<FileScript>,
+    /// Substitutions made by Chinese convertion of each file
+    pub(crate) reports: Vec<FileReport>,
+    /// Option rules matched by each file
+    pub(crate) rules: Vec<FileRule>,
+    /// Charset hints from ASS header used in detection of each file
+    pub(crate) charset_hints: Vec<FileCharsetHint>,
+    /// Encoding detection result of each file
+    pub(crate) detections: Vec<FileDetection>,
+    /// Files repaired from double encoding
+    pub(crate) repairs: Vec<FileRepair>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileScript {
+    pub(crate) filename: String,
+    pub(crate) script: Script,
+    /// False if skipped for already in the target script
+    pub(crate) converted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileRule {
+    pub(crate) filename: String,
+    /// Index of the rule in `Options::option_rules`
+    pub(crate) rule: usize,
+    pub(crate) pattern: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileCharsetHint {
+    pub(crate) filename: String,
+    /// Encoding implied by the hint
+    pub(crate) encoding: String,
+    /// True if taken as the result since the detector is unsure
+    pub(crate) fallback: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileDetection {
+    pub(crate) filename: String,
+    pub(crate) candidates: Vec<Candidate>,
+    /// True if the detector itself is sure about the encoding
+    pub(crate) sure: bool,
+    /// Encoding taken from the user fallback list, if any
+    pub(crate) fallback: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileRepair {
+    pub(crate) filename: String,
+    /// Original encoding of the mojibake
+    pub(crate) encoding: String,
+}
+
+/// Result of each file in the task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStatus {
+    pub(crate) path: String,
+    pub(crate) input_encoding: Option<String>,
+    pub(crate) output_encoding: Option<String>,
+    pub(crate) warnings: Vec<FileWarning>,
+    /// Failed file is left out of the output
+    pub(crate) error: Option<ConvertError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileWarning {
+    Decoding,
+    Encoding,
+}
+
+impl FileStatus {
+    fn done(path: String, meta: &ConvertMeta) -> Self {
+        let warnings = [
+            (meta.decode_error, FileWarning::Decoding),
+            (meta.encode_error, FileWarning::Encoding),
+        ];
+        Self {
+            path,
+            input_encoding: meta.input_encoding.iter().next().cloned(),
+            output_encoding: meta.output_encoding.iter().next().cloned(),
+            warnings: warnings
+                .into_iter()
+                .filter_map(|(has_error, warning)| Some(warning).take_if(|_| has_error))
+                .collect(),
+            error: None,
+        }
+    }
+
+    fn failed(path: String, error: ConvertError) -> Self {
+        Self {
+            path,
+            input_encoding: None,
+            output_encoding: None,
+            warnings: Vec::new(),
+            error: Some(error),
+        }
+    }
 }
 
 impl AddAssign for ConvertMeta {
@@ -111,6 +236,13 @@ impl AddAssign for ConvertMeta {
         self.output_encoding.extend(rhs.output_encoding);
         self.decode_error |= rhs.decode_error;
         self.encode_error |= rhs.encode_error;
+        self.dropped_cues += rhs.dropped_cues;
+        self.scripts.extend(rhs.scripts);
+        self.reports.extend(rhs.reports);
+        self.rules.extend(rhs.rules);
+        self.charset_hints.extend(rhs.charset_hints);
+        self.detections.extend(rhs.detections);
+        self.repairs.extend(rhs.repairs);
     }
 }
 
@@ -120,32 +252,19 @@ impl ConvertMeta {
     }
 }
 
-async fn fetch_opencc_dict(name: &str) -> Result<Dict, gloo_net::Error> {
-    let text = Request::get(name).send().await?.text().await?;
-    Ok(Dict::load_str(text))
-}
-
-pub async fn do_conversion_task(task: TaskRequest) -> Result<TaskResult, ConvertError> {
-    // load simpecc dict
-    let dict = match task.options.chinese_convertion.dict_name() {
-        Some(name) => Some(fetch_opencc_dict(name).await?),
-        None => None,
-    };
-
-    let check_file_size = |f: &File| {
-        let size = f.size() as usize;
-        if size > FILE_SIZE_LIMIT {
-            Err(ConvertError::TooLarge {
-                size,
-                limit: FILE_SIZE_LIMIT,
-            })
-        } else {
-            Ok(())
-        }
-    };
+/// `on_progress` is called before converting each file
+pub async fn do_conversion_task(
+    task: TaskRequest,
+    on_progress: impl Fn(TaskProgress),
+) -> Result<TaskResult, ConvertError> {
+    // load simpecc dicts, others may be loaded later for option rules
+    let mut converters = ChineseConverters::new(&task.user_dict);
+    converters
+        .get(task.options.chinese_convertion, None)
+        .await?;
 
     let reader = FileReaderSync::new()?;
-    let (content, filename, meta, mime) = if task.files.len() <= 1
+    let (content, filename, meta, files, mime) = if task.files.len() <= 1
         && !task.files[0]
             .0
             .name()
@@ -155,17 +274,29 @@ pub async fn do_conversion_task(task: TaskRequest) -> Result<TaskResult, Convert
         // case 1: single ass file, output srt file
         let file = &task.files.first().ok_or(ConvertError::NoFile)?.0;
         let input_buf = reader.read_to_vec(file)?;
-        let (output, meta) = convert_single_file(&input_buf, &task.options, &dict)?;
-        // set filename
-        let mut filename = file.name();
-        set_file_extension(&mut filename, "srt");
-        (output, filename, meta, MIME_SRT)
+        let name = file.name();
+        let tag = if task.options.filename_tags {
+            NameTag::parse(&name)
+        } else {
+            None
+        };
+        on_progress(TaskProgress {
+            files_discovered: 1,
+            current_file: Some(name.clone()),
+            ..Default::default()
+        });
+        let (output, filename, meta) = convert_file(
+            &name,
+            tag.as_ref(),
+            &input_buf,
+            &task.options,
+            &mut converters,
+        )
+        .await?;
+        let files = vec![FileStatus::done(name, &meta)];
+        (output, filename, meta, files, MIME_SRT)
     } else {
         // case 2: multiple ass files / zip files (single/multiple/mixed with ass), output zip file
-        // check file size
-        for FileWrap(file) in task.files.iter() {
-            check_file_size(file)?;
-        }
         // set filename
         let filename = if task.files.len() == 1 {
             // just single zip, append "_srt"
@@ -190,24 +321,70 @@ pub async fn do_conversion_task(task: TaskRequest) -> Result<TaskResult, Convert
                 format!("{}.zip", common)
             }
         };
-        // conversion
+        // conversion, failed files are left out
         let mut meta = ConvertMeta::default();
+        let mut files = Vec::new();
+        let mut progress = TaskProgress::default();
         let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
         let zip_file_opt =
             SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
-        for result in FileWalk::new(task.files, reader) {
-            let (mut path, buf) = result?;
-            path.set_extension("srt");
-            let (output, meta_) = convert_single_file(&buf, &task.options, &dict)?;
-            meta += meta_;
-            zip.start_file(path.to_string_lossy(), zip_file_opt)?;
-            zip.write_all(&output).map_err(ZipError::Io)?;
+        let mut output_names = HashSet::new();
+        let mut walk = FileWalk::new(task.files, reader, task.options.filename_tags);
+        while let Some(result) = walk.next() {
+            progress.files_discovered = walk.discovered();
+            let (path, buf, tag) = match result {
+                Ok(item) => item,
+                Err((path, err)) => {
+                    files.push(FileStatus::failed(path.to_string_lossy().into(), err));
+                    progress.files_done += 1;
+                    continue;
+                }
+            };
+            let name = path.to_string_lossy();
+            progress.current_file = Some(name.to_string());
+            on_progress(progress.clone());
+            progress.files_done += 1;
+            progress.bytes_processed += buf.len() as u64;
+            match convert_file(&name, tag.as_ref(), &buf, &task.options, &mut converters).await {
+                Ok((output, output_name, meta_)) => {
+                    // e.g. "ep.sc.ass" & "ep.tc.ass" both become "ep.tc.srt"
+                    let output_name = unique_name(output_name, &mut output_names);
+                    match zip_add_file(&mut zip, output_name, &output, zip_file_opt) {
+                        Ok(()) => {
+                            files.push(FileStatus::done(name.into(), &meta_));
+                            meta += meta_;
+                        }
+                        Err(err) => files.push(FileStatus::failed(name.into(), err.into())),
+                    }
+                }
+                Err(err) => files.push(FileStatus::failed(name.into(), err)),
+            }
+        }
+        // fail the task if nothing converted
+        if !files.is_empty() && files.iter().all(|f| f.error.is_some()) {
+            return Err(ConvertError::AllFailed(files));
+        }
+        let errors: Vec<_> = files
+            .iter()
+            .filter_map(|f| Some((&f.path, f.error.as_ref()?)))
+            .collect();
+        if task.options.zip_errors_file && !errors.is_empty() {
+            let text: String = errors
+                .iter()
+                .map(|(path, err)| format!("{path}: {err}\n"))
+                .collect();
+            let text = encode::set_line_ending(text, task.options.line_ending);
+            let name = ERRORS_FILENAME.to_string();
+            if let Err(err) = zip_add_file(&mut zip, name, text.as_bytes(), zip_file_opt) {
+                log::warn!("failed to add {} into zip: {}", ERRORS_FILENAME, err);
+            }
         }
         let zip = zip.finish()?;
         (
             zip.into_inner().into_boxed_slice(),
             filename,
             meta,
+            files,
             MIME_ZIP,
         )
     };
@@ -218,75 +395,197 @@ pub async fn do_conversion_task(task: TaskRequest) -> Result<TaskResult, Convert
         filename,
         file_url,
         meta,
+        files,
     })
 }
 
-fn detect_encoding(input: &[u8]) -> Option<&'static Encoding> {
-    let mut detector = EncodingDetector::new();
-    for chunk in input.chunks(256) {
-        if detector.feed(chunk, false) {
-            let (encoding, sure) = detector.guess_assess(None, true);
-            if sure {
-                return Some(encoding);
-            }
-        }
+/// Apply the matched option rule, then convert the file
+async fn convert_file(
+    name: &str,
+    tag: Option<&NameTag>,
+    input: &[u8],
+    opts: &Options,
+    converters: &mut ChineseConverters<'_>,
+) -> Result<(Box<[u8]>, String, ConvertMeta), ConvertError> {
+    let matched = option_rule::resolve(&opts.option_rules, name);
+    let opts = match matched {
+        Some((_, rule)) => Cow::Owned(rule.apply(opts)),
+        None => Cow::Borrowed(opts),
+    };
+    // filename tag tells the source script, so steps it's already past are skipped
+    let source = tag.map(|tag| tag.script);
+    let chinese = converters.get(opts.chinese_convertion, source).await?;
+    let (output, output_name, mut meta) = convert_single_file(name, tag, input, &opts, chinese)?;
+    if let Some((idx, rule)) = matched {
+        meta.rules.push(FileRule {
+            filename: name.to_string(),
+            rule: idx,
+            pattern: rule.pattern.clone(),
+        });
     }
-    detector.feed(&[], true);
-    let (encoding, sure) = detector.guess_assess(None, true);
-    Some(encoding).take_if(|_| sure)
+    Ok((output, output_name, meta))
 }
 
+/// Return (output, output filename, meta)
 fn convert_single_file(
+    name: &str,
+    tag: Option<&NameTag>,
     input: &[u8],
     opts: &Options,
-    dict: &Option<Dict>,
-) -> Result<(Box<[u8]>, ConvertMeta), ConvertError> {
+    chinese: &ChineseConverter,
+) -> Result<(Box<[u8]>, String, ConvertMeta), ConvertError> {
     let mut meta = ConvertMeta::default();
     // set encodings
     let ass_charset = if opts.ass_charset.is_empty() {
-        detect_encoding(input).ok_or(ConvertError::EncodingDetect)?
+        let hint = CharsetHint::find(input);
+        let detection = charset::detect_encoding(input, hint);
+        if let Some(hint) = hint {
+            meta.charset_hints.push(FileCharsetHint {
+                filename: name.to_string(),
+                encoding: hint.encoding.name().to_string(),
+                fallback: detection.hint_fallback,
+            });
+        }
+        // try user fallbacks if unsure or the guess is wrong (BOM always wins)
+        let fallbacks = charset::parse_labels(&opts.fallback_charsets)?;
+        let guess_ok = Encoding::for_bom(input).is_some()
+            || detection.encoding.is_some_and(|encoding| {
+                encoding
+                    .decode_without_bom_handling_and_without_replacement(input)
+                    .is_some()
+            });
+        let fallback = if guess_ok {
+            None
+        } else {
+            charset::try_fallbacks(input, &fallbacks)
+        };
+        meta.detections.push(FileDetection {
+            filename: name.to_string(),
+            candidates: detection.candidates,
+            sure: detection.sure,
+            fallback: fallback.map(|e| e.name().to_string()),
+        });
+        fallback
+            .or(detection.encoding)
+            .ok_or(ConvertError::EncodingDetect)?
     } else {
         Encoding::for_label(opts.ass_charset.as_bytes())
             .ok_or(ConvertError::EncodingLabel(opts.ass_charset.clone()))?
     };
-    let srt_charset = if opts.srt_charset.is_empty() {
-        UTF_8
+    // None for the same as input
+    let srt_charset = if opts.same_charset {
+        None
+    } else if opts.srt_charset.is_empty() {
+        Some(UTF_8)
     } else {
-        Encoding::for_label(opts.srt_charset.as_bytes())
-            .ok_or(ConvertError::EncodingLabel(opts.srt_charset.clone()))?
+        let encoding = Encoding::for_label(opts.srt_charset.as_bytes())
+            .ok_or(ConvertError::EncodingLabel(opts.srt_charset.clone()))?;
+        Some(encoding)
+    };
+
+    // decode
+    let (ass, ass_charset, has_error) = ass_charset.decode(input);
+    meta.input_encoding.insert(ass_charset.name().to_string());
+    meta.decode_error = has_error;
+    let srt_charset = srt_charset.unwrap_or(ass_charset);
+
+    // repair text that was decoded with wrong codepage & saved again
+    let repaired = Some(&ass)
+        .filter(|_| opts.mojibake_repair)
+        .and_then(|ass| mojibake::repair(ass));
+    let ass = match repaired {
+        Some((text, encoding)) => {
+            meta.repairs.push(FileRepair {
+                filename: name.to_string(),
+                encoding: encoding.name().to_string(),
+            });
+            Cow::Owned(text)
+        }
+        None => ass,
+    };
+
+    // detect chinese script, trust filename tag if any
+    let script = match tag {
+        Some(tag) if chinese.target().is_some() => Some(tag.script),
+        _ => chinese.detect(&ass),
     };
-    if srt_charset == UTF_16BE || srt_charset == UTF_16LE {
-        return Err(ConvertError::Utf16Output);
+    let convert_chinese = chinese.should_convert(script);
+    let mut output_name = match (tag, chinese.target()) {
+        (Some(tag), Some(target)) if convert_chinese => tag.retag(name, target),
+        _ => name.to_string(),
+    };
+    set_file_extension(&mut output_name, "srt");
+    if let Some(script) = script {
+        meta.scripts.push(FileScript {
+            filename: name.to_string(),
+            script,
+            converted: convert_chinese,
+        });
     }
 
-    // set text map (for line strip & chinese convertion)
+    // set text map (for normalization, sdh removal, line strip, replace rules
+    // & chinese convertion)
+    let replacer = TextReplacer::new(&opts.replace_rules)?;
+    let mut report = ReportBuilder::default();
     let text_map = for<'a> |text: Cow<'a, str>| -> Cow<'a, str> {
-        let text = opts.line_strip.strip(text);
-        if let Some(dict) = dict {
-            Cow::Owned(dict.replace_all(&text))
-        } else {
-            text
+        report.next_cue();
+        let text = opts.normalization.normalize(text);
+        let text = opts.sdh_removal.remove(text);
+        let mut text = opts.line_strip.strip(text);
+        if opts.replace_stage == ReplaceStage::BeforeConvertion {
+            text = replacer.replace(text);
+        }
+        if convert_chinese {
+            text = chinese.convert(text, |from, to| report.record(from, to));
         }
+        if opts.replace_stage == ReplaceStage::AfterConvertion {
+            text = replacer.replace(text);
+        }
+        text
     };
 
-    // decode & convert
-    let (ass, ass_charset, has_error) = ass_charset.decode(input);
-    meta.input_encoding.insert(ass_charset.name().to_string());
-    meta.decode_error = has_error;
+    // convert
     let offset_secs = (opts.offset_millis as f32) / 1000.0;
-    let srt = subtitle::ass_to_srt(&ass, true, Some(text_map), offset_secs)?;
+    let wrap = LineWrap {
+        max_chars: opts.max_line_chars as usize,
+        max_lines: opts.max_lines as usize,
+    };
+    let wrap = Some(&wrap).filter(|w| !w.is_disabled());
+    let cue_filter = CueFilter::new(&opts.drop_rules, opts.drop_credits)?;
+    let drop_cue = |style: &str, name: &str, text: &str| {
+        let matched = cue_filter.matches(style, name, text);
+        meta.dropped_cues += usize::from(matched);
+        matched
+    };
+    let Srt {
+        text: srt,
+        mapped_ids,
+    } = subtitle::ass_to_srt(&ass, true, Some(text_map), offset_secs, wrap, drop_cue)?;
+    if !report.is_empty() {
+        meta.reports
+            .push(report.build(name.to_string(), &mapped_ids));
+    }
 
     // encode
-    if srt_charset == UTF_8 {
-        meta.output_encoding.insert(srt_charset.name().to_string());
-        meta.encode_error = false;
-        Ok((srt.into_bytes().into_boxed_slice(), meta))
+    let srt = encode::set_line_ending(srt, opts.line_ending);
+    let (output, bom, has_error) = if srt_charset == UTF_8 {
+        let mut output = Vec::with_capacity(srt.len() + UTF8_BOM.len());
+        if opts.utf8_bom {
+            output.extend_from_slice(UTF8_BOM);
+        }
+        output.extend_from_slice(srt.as_bytes());
+        (output, opts.utf8_bom, false)
+    } else if srt_charset == UTF_16LE || srt_charset == UTF_16BE {
+        let output = encode::encode_utf16(&srt, srt_charset == UTF_16BE);
+        (output, true, false)
     } else {
-        let (output, srt_charset, has_error) = srt_charset.encode(&srt);
-        meta.output_encoding.insert(srt_charset.name().to_string());
-        meta.encode_error = has_error;
-        Ok((output.into_owned().into_boxed_slice(), meta))
-    }
+        let (output, _, has_error) = srt_charset.encode(&srt);
+        (output.into_owned(), false, has_error)
+    };
+    meta.output_encoding
+        .insert(encoding_label(srt_charset, bom, opts.line_ending));
+    meta.encode_error = has_error;
+    Ok((output.into_boxed_slice(), output_name, meta))
 }
 
 fn create_blob<T: AsRef<[u8]>>(buf: T, mime: &str) -> Result<Blob, JsValue> {
@@ -297,6 +596,52 @@ fn create_blob<T: AsRef<[u8]>>(buf: T, mime: &str) -> Result<Blob, JsValue> {
     Blob::new_with_u8_array_sequence_and_options(&blob_parts, &blob_opts)
 }
 
+/// Encoding name with BOM & line ending, e.g. "UTF-8 (BOM, CRLF)"
+fn encoding_label(encoding: &Encoding, bom: bool, line_ending: LineEnding) -> String {
+    let line_ending = match line_ending {
+        LineEnding::Crlf => "CRLF",
+        LineEnding::Lf => "LF",
+    };
+    if bom {
+        format!("{} (BOM, {})", encoding.name(), line_ending)
+    } else {
+        format!("{} ({})", encoding.name(), line_ending)
+    }
+}
+
+/// Write a file into zip, drop the partially written entry on error
+fn zip_add_file(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    name: String,
+    content: &[u8],
+    options: SimpleFileOptions,
+) -> Result<(), ZipError> {
+    let result = zip
+        .start_file(name, options)
+        .and_then(|()| zip.write_all(content).map_err(ZipError::Io));
+    if result.is_err() {
+        // nothing to abort if the entry failed to start
+        let _ = zip.abort_file();
+    }
+    result
+}
+
+/// Append " (n)" to the file stem if `name` is already taken
+fn unique_name(name: String, taken: &mut HashSet<String>) -> String {
+    let stem_start = name.rfind('/').map(|n| n + 1).unwrap_or(0);
+    let (stem, ext) = match name.rfind('.') {
+        Some(n) if n > stem_start => name.split_at(n),
+        _ => (name.as_str(), ""),
+    };
+    let mut unique = name.clone();
+    let mut n = 1;
+    while !taken.insert(unique.clone()) {
+        n += 1;
+        unique = format!("{stem} ({n}){ext}");
+    }
+    unique
+}
+
 /// Like PathBuf::set_extension but don't bother with OsStr
 fn set_file_extension(filename: &mut String, extension: &str) {
     if let Some(n) = filename.rfind('.') {
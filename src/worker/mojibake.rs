@@ -0,0 +1,187 @@
+use encoding_rs::{BIG5, Encoding, GB18030, SHIFT_JIS};
+
+use super::subtitle::is_cjk;
+
+/// Codepages the original bytes may be in, tried in order
+const ORIGINAL_ENCODINGS: &[&Encoding] = &[GB18030, BIG5, SHIFT_JIS];
+/// Chars of cp1252 0x80..=0x9F, `None` for undefined bytes
+const CP1252_HIGH: [Option<char>; 32] = [
+    Some('€'),
+    None,
+    Some('‚'),
+    Some('ƒ'),
+    Some('„'),
+    Some('…'),
+    Some('†'),
+    Some('‡'),
+    Some('ˆ'),
+    Some('‰'),
+    Some('Š'),
+    Some('‹'),
+    Some('Œ'),
+    None,
+    Some('Ž'),
+    None,
+    None,
+    Some('‘'),
+    Some('’'),
+    Some('“'),
+    Some('”'),
+    Some('•'),
+    Some('–'),
+    Some('—'),
+    Some('˜'),
+    Some('™'),
+    Some('š'),
+    Some('›'),
+    Some('œ'),
+    None,
+    Some('ž'),
+    Some('Ÿ'),
+];
+/// Min ratio of non-ASCII chars appearing in runs of two or more,
+/// as multibyte chars always result in such runs, unless the trail byte
+/// is in `ASCII_TRAIL`
+const MIN_RUN_RATIO: f32 = 0.9;
+/// ASCII bytes that can be the trail byte of GBK, Big5 & Shift_JIS chars
+const ASCII_TRAIL: std::ops::RangeInclusive<u8> = 0x40..=0x7E;
+/// Min ratio of CJK chars among non-ASCII chars in the repaired text
+const MIN_CJK_RATIO: f32 = 0.9;
+const MIN_CJK_CHARS: usize = 4;
+/// Min ratio of plausible chars among CJK chars in the repaired text, real
+/// text easily exceeds it while mojibake of another codepage mostly hits rare
+/// chars
+const MIN_PLAUSIBLE_RATIO: f32 = 0.15;
+/// Most frequent hanzi in simplified & traditional text, with kanji sharing
+/// the same forms
+const COMMON_HANZI: &str = "的一是不了人我在有他这個个们們中来來上大为為和国國地到以说說时時要就出会會\
+    可也你对對生能而子那得于於着著下自之年过過发發后後作里裡用道行所然家种種事成方多经經么麼\
+    去法学學如都同现現当當没沒动動面起看定天分还還进進好小部其些主样樣理心她本前开開但因只从從\
+    想实實日吗嗎呢吧啊什谁誰给給让讓跟怎哪這谢謝知走快真再已把被很话話门門问問间間听聽见見\
+    东東西头頭手长長回两兩先月";
+
+/// Hiragana & full-width katakana
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{30FF}')
+}
+
+/// Map char back to the byte of cp1252, or latin1 for the rest
+fn single_byte(c: char) -> Option<u8> {
+    if let Some(n) = CP1252_HIGH.iter().position(|h| *h == Some(c)) {
+        return Some(0x80 + n as u8);
+    }
+    u8::try_from(c).ok()
+}
+
+/// Return the original bytes if `text` looks like bytes of a multibyte
+/// encoding decoded as cp1252/latin1
+fn suspect_bytes(text: &str) -> Option<Vec<u8>> {
+    let bytes: Vec<u8> = text.chars().map(single_byte).collect::<Option<_>>()?;
+    let (mut non_ascii, mut in_runs, mut run) = (0, 0, 0);
+    for (i, &b) in bytes.iter().enumerate() {
+        if b.is_ascii() {
+            run = 0;
+            continue;
+        }
+        non_ascii += 1;
+        run += 1;
+        in_runs += match run {
+            // lead byte paired with an ASCII trail byte
+            1 if bytes.get(i + 1).is_some_and(|n| ASCII_TRAIL.contains(n)) => 1,
+            1 => 0,
+            2 => 2,
+            _ => 1,
+        };
+    }
+    let ratio = in_runs as f32 / non_ascii as f32;
+    Some(bytes).filter(|_| non_ascii > 0 && ratio >= MIN_RUN_RATIO)
+}
+
+/// Ratio of CJK chars among non-ASCII chars & the count of CJK chars
+fn cjk_ratio(text: &str) -> (f32, usize) {
+    let (mut non_ascii, mut cjk) = (0, 0);
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        non_ascii += 1;
+        cjk += usize::from(is_cjk(c));
+    }
+    if non_ascii == 0 {
+        return (0.0, 0);
+    }
+    (cjk as f32 / non_ascii as f32, cjk)
+}
+
+/// Ratio of plausible chars among CJK chars: common hanzi, plus kana if the
+/// text is decoded as Shift_JIS. Kana elsewhere is not credited, as Big5
+/// bytes often decode into the kana rows of GBK.
+fn plausible_ratio(text: &str, encoding: &Encoding) -> f32 {
+    let (mut cjk, mut plausible) = (0, 0);
+    for c in text.chars().filter(|&c| is_cjk(c)) {
+        cjk += 1;
+        if COMMON_HANZI.contains(c) || (encoding == SHIFT_JIS && is_kana(c)) {
+            plausible += 1;
+        }
+    }
+    if cjk == 0 {
+        return 0.0;
+    }
+    plausible as f32 / cjk as f32
+}
+
+/// Try to reverse the double encoding of `text`, return the repaired text
+/// & the original encoding. Only return text that is mostly CJK, picking the
+/// most plausible decoding when several codepages fit.
+pub(crate) fn repair(text: &str) -> Option<(String, &'static Encoding)> {
+    if text.chars().any(is_cjk) {
+        return None;
+    }
+    let bytes = suspect_bytes(text)?;
+    let mut best: Option<(f32, String, &'static Encoding)> = None;
+    for &encoding in ORIGINAL_ENCODINGS {
+        let Some(repaired) = encoding.decode_without_bom_handling_and_without_replacement(&bytes)
+        else {
+            continue;
+        };
+        let (ratio, cjk) = cjk_ratio(&repaired);
+        if ratio < MIN_CJK_RATIO || cjk < MIN_CJK_CHARS {
+            continue;
+        }
+        let plausible = plausible_ratio(&repaired, encoding);
+        if plausible < MIN_PLAUSIBLE_RATIO {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(p, _, _)| plausible > *p) {
+            best = Some((plausible, repaired.into_owned(), encoding));
+        }
+    }
+    best.map(|(_, text, encoding)| (text, encoding))
+}
+
+#[test]
+fn test_suspect_bytes() {
+    // "字幕" in GBK decoded as cp1252
+    assert_eq!(suspect_bytes("×ÖÄ»"), Some(b"\xd7\xd6\xc4\xbb".to_vec()));
+    assert_eq!(suspect_bytes("a€‚b"), Some(b"a\x80\x82b".to_vec()));
+    // "這是" in Big5, with ASCII trail bytes
+    assert_eq!(suspect_bytes("³o¬O"), Some(b"\xb3o\xacO".to_vec()));
+    // accented latin text
+    assert_eq!(suspect_bytes("Ça va, café ?"), None);
+    assert_eq!(suspect_bytes("ascii"), None);
+    assert_eq!(suspect_bytes("字幕"), None);
+}
+
+#[test]
+fn test_repair() {
+    let cases = [
+        ("这是一个中文字幕的测试，我们来看看", GB18030),
+        ("這是一個中文字幕的測試，我們來看看", BIG5),
+        ("これは日本語の字幕です。見てください", SHIFT_JIS),
+    ];
+    for (text, encoding) in cases {
+        let original = format!("Dialogue: 0:00:00.00,0:00:01.00,,{text}\n");
+        let (bytes, _, _) = encoding.encode(&original);
+        let mojibake: String = bytes.iter().map(|&b| char::from(b)).collect();
+        assert_eq!(repair(&mojibake), Some((original.clone(), encoding)));
+        assert_eq!(repair(&original), None);
+    }
+    assert_eq!(repair("Ça va, café ?"), None);
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use crate::Script;
+
+/// Pairs of (Simplified, Traditional) tags after a dot, e.g. "name.sc.ass"
+const DOT_TAGS: &[(&str, &str)] = &[
+    ("sc", "tc"),
+    ("chs", "cht"),
+    ("jpsc", "jptc"),
+    ("gb", "big5"),
+    ("zh-Hans", "zh-Hant"),
+    ("zh-CN", "zh-TW"),
+];
+/// Pairs of (Simplified, Traditional) tags in brackets, e.g. "[简体] name.ass"
+const BRACKET_TAGS: &[(&str, &str)] = &[("简体", "繁体"), ("簡體", "繁體"), ("简", "繁")];
+const BRACKETS: &[(char, char)] = &[('[', ']'), ('【', '】')];
+
+/// Chinese language tag found in filename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NameTag {
+    /// Byte range of the tag in the path
+    range: Range<usize>,
+    pair: (&'static str, &'static str),
+    /// Script of the file claimed by the tag
+    pub(crate) script: Script,
+}
+
+fn find_tag(word: &str, tags: &'static [(&'static str, &'static str)]) -> Option<NameTag> {
+    tags.iter().find_map(|&pair| {
+        let script = if word.eq_ignore_ascii_case(pair.0) {
+            Script::Simplified
+        } else if word.eq_ignore_ascii_case(pair.1) {
+            Script::Traditional
+        } else {
+            return None;
+        };
+        Some(NameTag {
+            range: 0..word.len(),
+            pair,
+            script,
+        })
+    })
+}
+
+/// Follow the case of `original` if it's all in lower/upper case
+fn match_case(original: &str, tag: &str) -> String {
+    if original.chars().any(|c| c.is_ascii_uppercase()) {
+        if !original.chars().any(|c| c.is_ascii_lowercase()) {
+            return tag.to_ascii_uppercase();
+        }
+    } else if original.chars().any(|c| c.is_ascii_lowercase()) {
+        return tag.to_ascii_lowercase();
+    }
+    tag.to_string()
+}
+
+impl NameTag {
+    /// Find tag in the filename (last component) of `path`
+    pub(crate) fn parse(path: &str) -> Option<Self> {
+        let start = path.rfind('/').map(|n| n + 1).unwrap_or(0);
+        let name = &path[start..];
+        let stem = name.rfind('.').map(|n| &name[..n]).unwrap_or(name);
+        // dotted tags, check the one closest to the extension first
+        let mut end = stem.len();
+        while let Some(dot) = stem[..end].rfind('.') {
+            if let Some(mut tag) = find_tag(&stem[dot + 1..end], DOT_TAGS) {
+                tag.range = start + dot + 1..start + end;
+                return Some(tag);
+            }
+            end = dot;
+        }
+        // bracketed tags
+        for &(open, close) in BRACKETS {
+            for (idx, _) in stem.match_indices(open) {
+                let word_start = idx + open.len_utf8();
+                let Some(len) = stem[word_start..].find(close) else {
+                    continue;
+                };
+                let word = &stem[word_start..word_start + len];
+                if let Some(mut tag) = find_tag(word, BRACKET_TAGS) {
+                    tag.range = start + word_start..start + word_start + len;
+                    return Some(tag);
+                }
+            }
+        }
+        None
+    }
+
+    /// Replace the tag in `path` with the one of `script`
+    pub(crate) fn retag(&self, path: &str, script: Script) -> String {
+        let tag = match script {
+            Script::Simplified => self.pair.0,
+            Script::Traditional => self.pair.1,
+            Script::Mixed | Script::Unknown => return path.to_string(),
+        };
+        let (head, tail) = (&path[..self.range.start], &path[self.range.end..]);
+        let original = &path[self.range.clone()];
+        format!("{}{}{}", head, match_case(original, tag), tail)
+    }
+}
+
+#[test]
+fn test_name_tag_parse() {
+    let tag = NameTag::parse("dir.sc/Movie.2024.SC.ass").unwrap();
+    assert_eq!(tag.script, Script::Simplified);
+    assert_eq!(
+        tag.retag("dir.sc/Movie.2024.SC.ass", Script::Traditional),
+        "dir.sc/Movie.2024.TC.ass"
+    );
+
+    let name = "Movie.zh-Hant.default.ass";
+    let tag = NameTag::parse(name).unwrap();
+    assert_eq!(tag.script, Script::Traditional);
+    assert_eq!(
+        tag.retag(name, Script::Simplified),
+        "Movie.zh-Hans.default.ass"
+    );
+
+    let name = "[简体] 电影 [1080p].ass";
+    let tag = NameTag::parse(name).unwrap();
+    assert_eq!(tag.script, Script::Simplified);
+    assert_eq!(
+        tag.retag(name, Script::Traditional),
+        "[繁体] 电影 [1080p].ass"
+    );
+
+    assert_eq!(
+        NameTag::parse("ep01.jpsc.ass").map(|t| t.script),
+        Some(Script::Simplified)
+    );
+    assert_eq!(NameTag::parse("sc.ass"), None);
+    assert_eq!(NameTag::parse("abc.ass"), None);
+}
@@ -0,0 +1,72 @@
+use crate::OptionRule;
+
+/// Match glob `pattern` against `text`, ASCII case-insensitively.
+/// `*` & `?` never match `/`, while `**` does.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // zero or more directories
+        ['*', '*', '/', rest @ ..] => (0..=text.len())
+            .filter(|&n| n == 0 || text[n - 1] == '/')
+            .any(|n| glob_match(rest, &text[n..])),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|n| glob_match(rest, &text[n..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&n| n == 0 || text[n - 1] != '/')
+            .any(|n| glob_match(rest, &text[n..])),
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && glob_match(rest, &text[1..]),
+        [p, rest @ ..] => {
+            matches!(text, [c, ..] if c.eq_ignore_ascii_case(p)) && glob_match(rest, &text[1..])
+        }
+    }
+}
+
+/// Find the first rule matching `path`, with its index
+pub(crate) fn resolve<'a>(rules: &'a [OptionRule], path: &str) -> Option<(usize, &'a OptionRule)> {
+    let filename = path.rsplit('/').next().unwrap_or(path);
+    let path: Vec<char> = path.chars().collect();
+    let filename: Vec<char> = filename.chars().collect();
+    rules
+        .iter()
+        .enumerate()
+        .filter(|(_, rule)| !rule.pattern.is_empty())
+        .find(|(_, rule)| {
+            let pattern: Vec<char> = rule.pattern.chars().collect();
+            let text = if pattern.contains(&'/') {
+                &path
+            } else {
+                &filename
+            };
+            glob_match(&pattern, text)
+        })
+}
+
+#[test]
+fn test_glob_match() {
+    let matches = |pattern: &str, text: &str| {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob_match(&pattern, &text)
+    };
+    assert!(matches("*.ass", "a.ASS"));
+    assert!(matches("[GroupA]*", "[GroupA] ep01.ass"));
+    assert!(matches("ep??.ass", "ep01.ass"));
+    assert!(!matches("ep??.ass", "ep1.ass"));
+    assert!(!matches("*.ass", "dir/a.ass"));
+    assert!(matches("dir/*.ass", "dir/a.ass"));
+    assert!(matches("**/*.ass", "a.ass"));
+    assert!(matches("**/*.ass", "a/b/c.ass"));
+    assert!(matches("a/**", "a/b/c.ass"));
+}
+
+#[test]
+fn test_resolve_option_rules() {
+    let rule = |pattern: &str| OptionRule {
+        pattern: pattern.into(),
+        ..Default::default()
+    };
+    let rules = [rule(""), rule("*.chs.ass"), rule("bilingual/**"), rule("*")];
+    assert_eq!(resolve(&rules, "x/ep01.chs.ass").map(|r| r.0), Some(1));
+    assert_eq!(resolve(&rules, "bilingual/ep01.ass").map(|r| r.0), Some(2));
+    assert_eq!(resolve(&rules, "ep01.ass").map(|r| r.0), Some(3));
+    assert_eq!(resolve(&rules[..3], "ep01.ass"), None);
+}
@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+
+use regex_lite::Regex;
+
+use super::ConvertError;
+use crate::ReplaceRule;
+
+/// Compiled list of [ReplaceRule]
+#[derive(Debug, Default)]
+pub(crate) struct TextReplacer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl TextReplacer {
+    /// Compile rules, rules with empty pattern are ignored
+    pub(crate) fn new(rules: &[ReplaceRule]) -> Result<Self, ConvertError> {
+        let rules = rules
+            .iter()
+            .filter(|rule| !rule.pattern.is_empty())
+            .map(|rule| match rule.compile() {
+                Ok(re) => Ok((re, rule.replacement.clone())),
+                Err(err) => Err(ConvertError::Regex {
+                    pattern: rule.pattern.clone(),
+                    msg: err.to_string(),
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub(crate) fn replace<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        self.rules.iter().fold(text, |text, (re, rep)| {
+            let replaced = match re.replace_all(&text, rep.as_str()) {
+                Cow::Borrowed(_) => None,
+                Cow::Owned(replaced) => Some(replaced),
+            };
+            replaced.map(Cow::Owned).unwrap_or(text)
+        })
+    }
+}
+
+#[test]
+fn test_text_replacer() {
+    let rule = |pattern: &str, replacement: &str| ReplaceRule {
+        pattern: pattern.to_string(),
+        replacement: replacement.to_string(),
+    };
+    let replacer = TextReplacer::new(&[
+        rule(r"\.{3}", "…"),
+        rule("", "ignored"),
+        rule(r"(\w+)@(\w+)", "$2 at $1"),
+    ])
+    .unwrap();
+    assert_eq!(replacer.replace("Wait...".into()), "Wait…");
+    assert_eq!(replacer.replace("a@b...".into()), "b at a…");
+    assert!(matches!(
+        replacer.replace("untouched".into()),
+        Cow::Borrowed(_)
+    ));
+
+    assert!(matches!(
+        TextReplacer::new(&[rule("(", "")]),
+        Err(ConvertError::Regex { .. })
+    ));
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Max number of substitutions kept in the report of each file
+const MAX_SUBSTITUTIONS: usize = 100;
+/// Max number of cue positions kept for each substitution
+const MAX_CUES: usize = 10;
+
+/// Substitutions made by Chinese convertion on a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FileReport {
+    pub(crate) filename: String,
+    /// Sorted by count in descending order
+    pub(crate) substitutions: Vec<Substitution>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Substitution {
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) count: usize,
+    /// Ids of SRT cues where it occurs
+    pub(crate) cues: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ReportBuilder {
+    /// Number of mapped cues so far, cues are numbered with it until build
+    cue: usize,
+    substitutions: HashMap<(String, String), (usize, Vec<usize>)>,
+}
+
+impl ReportBuilder {
+    /// Move on to the next cue
+    pub(crate) fn next_cue(&mut self) {
+        self.cue += 1;
+    }
+
+    pub(crate) fn record(&mut self, from: &str, to: &str) {
+        let (count, cues) = self
+            .substitutions
+            .entry((from.to_string(), to.to_string()))
+            .or_default();
+        *count += 1;
+        if cues.len() < MAX_CUES && cues.last() != Some(&self.cue) {
+            cues.push(self.cue);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.substitutions.is_empty()
+    }
+
+    /// `cue_ids` maps each cue to its SRT id, see `subtitle::Srt::mapped_ids`.
+    /// Positions in cues left out of the output are removed.
+    pub(crate) fn build(self, filename: String, cue_ids: &[Option<usize>]) -> FileReport {
+        let mut substitutions: Vec<_> = self
+            .substitutions
+            .into_iter()
+            .map(|((from, to), (count, cues))| Substitution {
+                from,
+                to,
+                count,
+                cues: cues
+                    .into_iter()
+                    .filter_map(|cue| cue_ids.get(cue - 1).copied().flatten())
+                    .collect(),
+            })
+            .collect();
+        substitutions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.from.cmp(&b.from)));
+        substitutions.truncate(MAX_SUBSTITUTIONS);
+        FileReport {
+            filename,
+            substitutions,
+        }
+    }
+}
+
+#[test]
+fn test_report_builder() {
+    let mut builder = ReportBuilder::default();
+    assert!(builder.is_empty());
+    builder.next_cue();
+    builder.record("后", "後");
+    builder.record("后", "後");
+    builder.next_cue();
+    builder.record("着", "著");
+    builder.next_cue();
+    builder.record("后", "後");
+    // the 2nd cue is left out, the 1st one is split into two
+    let report = builder.build("a.ass".into(), &[Some(1), None, Some(3)]);
+    assert_eq!(
+        report.substitutions,
+        [
+            Substitution {
+                from: "后".into(),
+                to: "後".into(),
+                count: 3,
+                cues: vec![1, 3],
+            },
+            Substitution {
+                from: "着".into(),
+                to: "著".into(),
+                count: 1,
+                cues: vec![],
+            },
+        ]
+    );
+}
@@ -9,12 +9,17 @@ use thiserror::Error;
 mod lines;
 #[cfg(test)]
 mod tests;
+mod wrap;
+
+pub(crate) use wrap::{LineWrap, is_cjk};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, AsRefStr, Display)]
 #[strum(serialize_all = "lowercase")]
 pub enum Field {
     Start,
     End,
+    Style,
+    Name,
     Text,
     Effect,
 }
@@ -42,6 +47,8 @@ struct Centisec(u32);
 struct Dialogue<'a> {
     start: Centisec,
     end: Centisec,
+    style: &'a str,
+    name: &'a str,
     text: Cow<'a, str>,
     effect: bool,
 }
@@ -73,12 +80,16 @@ impl DialogueFormat {
         let start = req_field(Field::Start)?.parse()?;
         let end = req_field(Field::End)?.parse()?;
         let text = req_field(Field::Text)?;
+        let style = opt_field(Field::Style).copied().unwrap_or_default();
+        let name = opt_field(Field::Name).copied().unwrap_or_default();
         let effect = opt_field(Field::Effect)
             .map(|t| !t.trim().is_empty())
             .unwrap_or(false);
         Ok(Dialogue {
             start,
             end,
+            style,
+            name,
             effect,
             text: Cow::from(*text),
         })
@@ -106,6 +117,50 @@ impl Dialogue<'_> {
         .into();
     }
 
+    /// Re-break text with `wrap`, split into multiple dialogues if it has too
+    /// many lines. Duration is distributed in proportion to the text length,
+    /// each dialogue lasts at least 1 centisecond.
+    fn wrap(self, wrap: &LineWrap) -> Vec<Self> {
+        let mut chunks = wrap.apply(&self.text);
+        let start = self.start.0;
+        let duration = self.end.0.saturating_sub(start) as u64;
+        let n = chunks.len() as u64;
+        if n > duration {
+            // too short to split, keep all lines in one dialogue
+            let text = chunks.join("\r\n");
+            chunks = vec![text];
+        }
+        if chunks.len() <= 1 {
+            let text = chunks.pop().map(Cow::Owned).unwrap_or(self.text);
+            return vec![Dialogue { text, ..self }];
+        }
+        let text_len = |text: &str| text.chars().filter(|c| !matches!(c, '\r' | '\n')).count();
+        let total = chunks.iter().map(|c| text_len(c)).sum::<usize>().max(1) as u64;
+        let (mut done, mut end) = (0, 0);
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(k, text)| {
+                let begin = end;
+                done += text_len(&text) as u64;
+                // leave at least 1 centisecond for each of the rest
+                let rest = n - k as u64 - 1;
+                end = (duration * done / total)
+                    .max(begin + 1)
+                    .min(duration - rest);
+                let (begin, end) = (start + begin as u32, start + end as u32);
+                Dialogue {
+                    start: Centisec(begin),
+                    end: Centisec(end),
+                    style: self.style,
+                    name: self.name,
+                    text: text.into(),
+                    effect: self.effect,
+                }
+            })
+            .collect()
+    }
+
     fn as_srt(&self, id: usize) -> String {
         format!(
             "{}\r\n{} --> {}\r\n{}\r\n\r\n",
@@ -160,21 +215,46 @@ impl Centisec {
     }
 }
 
-pub fn ass_to_srt<'a: 'b, 'b, F>(
+/// Lines within [Events]
+fn events(ass: &str) -> impl Iterator<Item = &str> {
+    UniversalLines::new(ass)
+        .skip_while(|l| !l.starts_with("[Events]"))
+        .skip(1)
+        .take_while(|l| !l.starts_with("["))
+        .map(|l| l.trim())
+}
+
+/// Check if `ass` has [Events] section with a valid format line
+pub(crate) fn has_events(ass: &str) -> bool {
+    events(ass)
+        .find(|l| l.starts_with("Format:"))
+        .is_some_and(|format| DialogueFormat::new(format).is_ok())
+}
+
+/// Output of [`ass_to_srt`]
+pub struct Srt {
+    pub text: String,
+    /// SRT id of the first cue made from each dialogue passed to `mapper`,
+    /// in the order of calls. None if the dialogue is left out.
+    pub mapped_ids: Vec<Option<usize>>,
+}
+
+/// Convert ASS to SRT.
+/// `drop_cue` is called with (style, name, text) of each dialogue before
+/// `mapper`; the dialogue is dropped if it returns true.
+pub fn ass_to_srt<'a: 'b, 'b, F, D>(
     ass: &'a str,
     no_effect: bool,
     mut mapper: Option<F>,
     offset_secs: f32,
-) -> Result<String, FormatError>
+    wrap: Option<&LineWrap>,
+    mut drop_cue: D,
+) -> Result<Srt, FormatError>
 where
     F: FnMut(Cow<'b, str>) -> Cow<'b, str>,
+    D: FnMut(&str, &str, &str) -> bool,
 {
-    // find lines within [Events]
-    let mut events = UniversalLines::new(ass)
-        .skip_while(|l| !l.starts_with("[Events]"))
-        .skip(1)
-        .take_while(|l| !l.starts_with("["))
-        .map(|l| l.trim());
+    let mut events = events(ass);
     // find format line
     let format = events
         .find(|l| l.starts_with("Format:"))
@@ -187,27 +267,50 @@ where
         .filter_map(|d| d.ok())
         .filter(|d| !no_effect || !d.effect)
         .collect::<Vec<_>>();
-    // to srt
     dialogues.sort();
-    let mut id = 0;
-    Ok(dialogues
+    // map text, keep index of the mapper call with each dialogue
+    let mut mapped_ids = Vec::new();
+    let dialogues: Vec<_> = dialogues
         .into_iter()
         .filter_map(|mut d| {
             d.cleanse_text();
-            if d.text.is_empty() {
+            if d.text.is_empty() || drop_cue(d.style, d.name, &d.text) {
                 return None;
             }
             d.start.add_secs(offset_secs);
             d.end.add_secs(offset_secs);
+            let mut idx = None;
             if let Some(ref mut f) = mapper {
                 d.text = f(d.text);
+                idx = Some(mapped_ids.len());
+                mapped_ids.push(None);
+                // mapper may remove all text
+                if d.text.trim().is_empty() {
+                    return None;
+                }
             }
-            Some(d)
+            Some((idx, d))
+        })
+        .collect();
+    // to srt
+    let mut id = 0;
+    let text = dialogues
+        .into_iter()
+        .flat_map(|(idx, d)| {
+            let dialogues = match wrap {
+                Some(wrap) => d.wrap(wrap),
+                None => vec![d],
+            };
+            dialogues.into_iter().map(move |d| (idx, d))
         })
-        .filter(|d| d.end.0 > d.start.0)
-        .map(|d| {
+        .filter(|(_, d)| d.end.0 > d.start.0)
+        .map(|(idx, d)| {
             id += 1;
+            if let Some(idx) = idx {
+                mapped_ids[idx].get_or_insert(id);
+            }
             d.as_srt(id)
         })
-        .collect())
+        .collect();
+    Ok(Srt { text, mapped_ids })
 }
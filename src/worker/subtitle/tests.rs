@@ -1,4 +1,6 @@
-use super::{Centisec, Dialogue, ass_to_srt};
+use std::borrow::Cow;
+
+use super::{Centisec, Dialogue, LineWrap, ass_to_srt, has_events};
 
 const ASS_SAMPLE: &str = r#"
 ; 啊啊啊啊啊
@@ -16,6 +18,8 @@ fn test_cleanse_text() {
     let mut d = Dialogue {
         start: Centisec(0),
         end: Centisec(0),
+        style: "",
+        name: "",
         effect: false,
         text: r"some{\fad(2,5)\p1\alpha&5}few{\p2}draw{\p0}{\b0\test}text{\b1}{\p0}\Nline".into(),
     };
@@ -36,7 +40,9 @@ Something...\r\n\r\n\
 00:04:01,000 --> 00:04:02,000\r\n\
 sometext\r\n\r\n";
     let conv = |s| s;
-    let result = ass_to_srt(ASS_SAMPLE, true, Some(conv), 0.0).unwrap();
+    let result = ass_to_srt(ASS_SAMPLE, true, Some(conv), 0.0, None, |_, _, _| false)
+        .unwrap()
+        .text;
     assert_eq!(result, srt);
 }
 
@@ -45,6 +51,128 @@ fn test_ass_line_ending() {
     let crlf = ASS_SAMPLE.replace('\n', "\r\n");
     let lf = ASS_SAMPLE.replace('\n', "\r");
     let conv = |s| s;
-    assert!(ass_to_srt(&crlf, true, Some(conv), 0.0).is_ok());
-    assert!(ass_to_srt(&lf, true, Some(conv), 0.0).is_ok());
+    assert!(ass_to_srt(&crlf, true, Some(conv), 0.0, None, |_, _, _| false).is_ok());
+    assert!(ass_to_srt(&lf, true, Some(conv), 0.0, None, |_, _, _| false).is_ok());
+}
+
+#[test]
+fn test_has_events() {
+    assert!(has_events(ASS_SAMPLE));
+    assert!(!has_events("[Events]\nFormat: Start, End\n"));
+    assert!(!has_events("[Script Info]\nFormat: Start, End, Text\n"));
+}
+
+#[test]
+fn test_ass_to_srt_drop_emptied() {
+    let srt = "\
+1\r\n\
+00:02:42,420 --> 00:02:44,050\r\n\
+Something...\r\n\r\n";
+    let conv = |s: Cow<'static, str>| if s.starts_with('S') { s } else { "".into() };
+    let result = ass_to_srt(ASS_SAMPLE, true, Some(conv), 0.0, None, |_, _, _| false)
+        .unwrap()
+        .text;
+    assert_eq!(result, srt);
+}
+
+#[test]
+fn test_ass_to_srt_drop_cue() {
+    let srt = "\
+1\r\n\
+00:02:40,650 --> 00:02:41,790\r\n\
+Hello,\r\nworld!~\r\n\r\n";
+    let conv = |s| s;
+    let mut dropped = vec![];
+    let drop_cue = |style: &str, name: &str, text: &str| {
+        assert_eq!(style, "main");
+        if name == "b" || text.starts_with("some") {
+            dropped.push(text.to_string());
+            true
+        } else {
+            false
+        }
+    };
+    let result = ass_to_srt(ASS_SAMPLE, true, Some(conv), 0.0, None, drop_cue)
+        .unwrap()
+        .text;
+    assert_eq!(result, srt);
+    assert_eq!(dropped, ["Something...", "sometext"]);
+}
+
+#[test]
+fn test_ass_to_srt_wrap() {
+    let ass = "\
+[Events]
+Format: Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0:00:01.00,0:00:04.00,main,a,0,0,0,,一二三四五六七八九十\\Nabcde
+";
+    let srt = "\
+1\r\n\
+00:00:01,000 --> 00:00:03,000\r\n\
+一二三四五\r\n六七八九十\r\n\r\n\
+2\r\n\
+00:00:03,000 --> 00:00:04,000\r\n\
+abcde\r\n\r\n";
+    let wrap = LineWrap {
+        max_chars: 5,
+        max_lines: 2,
+    };
+    let conv = |s| s;
+    let result = ass_to_srt(ass, true, Some(conv), 0.0, Some(&wrap), |_, _, _| false)
+        .unwrap()
+        .text;
+    assert_eq!(result, srt);
+}
+
+#[test]
+fn test_ass_to_srt_wrap_short_cue() {
+    let wrap = LineWrap {
+        max_chars: 5,
+        max_lines: 1,
+    };
+    let conv = |s| s;
+    // every chunk lasts at least 1 centisecond
+    let ass = "\
+[Events]
+Format: Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0:00:01.00,0:00:01.04,main,a,0,0,0,,一二三四五六七八九十\\Na\\Nb
+";
+    let result = ass_to_srt(ass, true, Some(conv), 0.0, Some(&wrap), |_, _, _| false)
+        .unwrap()
+        .text;
+    assert_eq!(result.matches(" --> ").count(), 4);
+    assert!(result.contains("00:00:01,030 --> 00:00:01,040\r\nb\r\n"));
+    // shorter than the number of chunks, keep lines together
+    let ass = ass.replace("0:00:01.04", "0:00:01.03");
+    let result = ass_to_srt(&ass, true, Some(conv), 0.0, Some(&wrap), |_, _, _| false)
+        .unwrap()
+        .text;
+    assert_eq!(result.matches(" --> ").count(), 1);
+    assert!(result.contains("一二三四五\r\n六七八九十\r\na\r\nb\r\n"));
+}
+
+#[test]
+fn test_ass_to_srt_mapped_ids() {
+    let ass = "\
+[Events]
+Format: Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+Dialogue: 0:00:01.00,0:00:02.00,main,a,0,0,0,,dropped
+Dialogue: 0:00:03.00,0:00:05.00,main,a,0,0,0,,一二三四五六七八九十
+Dialogue: 0:00:06.00,0:00:07.00,main,a,0,0,0,,emptied
+Dialogue: 0:00:08.00,0:00:09.00,main,a,0,0,0,,last
+";
+    let wrap = LineWrap {
+        max_chars: 5,
+        max_lines: 1,
+    };
+    let conv = |s: Cow<'static, str>| if s == "emptied" { "".into() } else { s };
+    let drop_cue = |_: &str, _: &str, text: &str| text == "dropped";
+    let srt = ass_to_srt(ass, true, Some(conv), 0.0, Some(&wrap), drop_cue).unwrap();
+    assert_eq!(srt.text.matches(" --> ").count(), 3);
+    assert!(
+        srt.text
+            .contains("3\r\n00:00:08,000 --> 00:00:09,000\r\nlast\r\n")
+    );
+    // mapper is called on the 2nd to 4th dialogues
+    assert_eq!(srt.mapped_ids, [Some(1), None, Some(3)]);
 }
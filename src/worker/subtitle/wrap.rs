@@ -0,0 +1,207 @@
+/// Characters that must not begin a line (closing brackets & punctuations)
+const NO_LINE_START: &str = "!%),.:;?]}¢°·’”…‥、。〉》」』】〕〗〙〛・ー々ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ！％），．：；？］｝～";
+/// Characters that must not end a line (opening brackets)
+const NO_LINE_END: &str = "$([{£¥‘“〈《「『【〔〖〘〚（［｛＄";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LineWrap {
+    /// Max number of characters per line, 0 for unlimited
+    pub(crate) max_chars: usize,
+    /// Max number of lines per cue, 0 for unlimited
+    pub(crate) max_lines: usize,
+}
+
+impl LineWrap {
+    pub(crate) fn is_disabled(&self) -> bool {
+        self.max_chars == 0 && self.max_lines == 0
+    }
+
+    /// Re-break lines that exceed `max_chars`, then group them into chunks of
+    /// at most `max_lines` lines. Each chunk is supposed to be a separate cue.
+    pub(crate) fn apply(&self, text: &str) -> Vec<String> {
+        let lines: Vec<String> = text
+            .lines()
+            .flat_map(|line| {
+                if self.max_chars == 0 {
+                    vec![line.to_string()]
+                } else {
+                    wrap_line(line, self.max_chars)
+                }
+            })
+            .collect();
+        if self.max_lines == 0 || lines.len() <= self.max_lines {
+            vec![lines.join("\r\n")]
+        } else {
+            lines
+                .chunks(self.max_lines)
+                .map(|chunk| chunk.join("\r\n"))
+                .collect()
+        }
+    }
+}
+
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{303F}' // CJK radicals, symbols & punctuation
+        | '\u{3040}'..='\u{31FF}' // kana, bopomofo, etc.
+        | '\u{3400}'..='\u{4DBF}' // CJK ext-A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FE30}'..='\u{FE4F}' // CJK compatibility forms
+        | '\u{FF00}'..='\u{FFEF}' // half/full-width forms
+        | '\u{20000}'..='\u{3FFFF}' // CJK ext-B and beyond
+    )
+}
+
+fn can_break_between(prev: char, next: char) -> bool {
+    (is_cjk(prev) || is_cjk(next)) && !NO_LINE_START.contains(next) && !NO_LINE_END.contains(prev)
+}
+
+/// Unbreakable piece of text
+struct Atom<'a> {
+    text: &'a str,
+    width: usize,
+    space_before: bool,
+}
+
+/// Push text as atom(s), forcibly split it if wider than `max_chars`
+fn push_atoms<'a>(
+    atoms: &mut Vec<Atom<'a>>,
+    text: &'a str,
+    mut space_before: bool,
+    max_chars: usize,
+) {
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest
+            .char_indices()
+            .nth(max_chars)
+            .map(|(n, _)| n)
+            .unwrap_or(rest.len());
+        let (text, tail) = rest.split_at(end);
+        atoms.push(Atom {
+            text,
+            width: text.chars().count(),
+            space_before,
+        });
+        space_before = false;
+        rest = tail;
+    }
+}
+
+/// Split line into atoms at spaces and between CJK characters
+fn split_atoms(line: &str, max_chars: usize) -> Vec<Atom<'_>> {
+    let mut atoms = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut space_before = false;
+    let mut prev: Option<char> = None;
+    for (idx, c) in line.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                push_atoms(&mut atoms, &line[s..idx], space_before, max_chars);
+            }
+            space_before = true;
+        } else {
+            match (start, prev) {
+                (Some(s), Some(p)) if can_break_between(p, c) => {
+                    push_atoms(&mut atoms, &line[s..idx], space_before, max_chars);
+                    space_before = false;
+                    start = Some(idx);
+                }
+                (Some(_), _) => (),
+                (None, _) => start = Some(idx),
+            }
+        }
+        prev = Some(c);
+    }
+    if let Some(s) = start {
+        push_atoms(&mut atoms, &line[s..], space_before, max_chars);
+    }
+    atoms
+}
+
+/// Greedily fill atoms into lines no wider than `width`
+fn break_atoms(atoms: &[Atom], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for atom in atoms {
+        let space = usize::from(atom.space_before && !line.is_empty());
+        if !line.is_empty() && line_width + space + atom.width > width {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        } else if space > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(atom.text);
+        line_width += atom.width;
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Break a single line into balanced lines of at most `max_chars` characters
+fn wrap_line(line: &str, max_chars: usize) -> Vec<String> {
+    if line.chars().count() <= max_chars {
+        return vec![line.to_string()];
+    }
+    let atoms = split_atoms(line, max_chars);
+    let greedy = break_atoms(&atoms, max_chars);
+    let total: usize = atoms
+        .iter()
+        .enumerate()
+        .map(|(i, a)| a.width + usize::from(i > 0 && a.space_before))
+        .sum();
+    // find the narrowest width that keeps the same number of lines
+    let n = greedy.len();
+    (total.div_ceil(n.max(1)).max(1)..max_chars)
+        .map(|width| break_atoms(&atoms, width))
+        .find(|lines| lines.len() <= n)
+        .unwrap_or(greedy)
+}
+
+#[test]
+fn test_wrap_line_words() {
+    assert_eq!(wrap_line("short line", 20), vec!["short line"]);
+    assert_eq!(
+        wrap_line("the quick brown fox jumps over the lazy dog", 30),
+        vec!["the quick brown fox", "jumps over the lazy dog"]
+    );
+    assert_eq!(wrap_line("abcdefgh", 3), vec!["abc", "def", "gh"]);
+}
+
+#[test]
+fn test_wrap_line_cjk() {
+    assert_eq!(
+        wrap_line("我们今天去公园散步吧", 6),
+        vec!["我们今天去", "公园散步吧"]
+    );
+    // never start a line with closing punctuation
+    assert_eq!(
+        wrap_line("你好，世界。再见！", 4),
+        vec!["你好，", "世界。", "再见！"]
+    );
+    // never end a line with opening bracket
+    assert_eq!(wrap_line("他说「你好」", 3), vec!["他说", "「你", "好」"]);
+}
+
+#[test]
+fn test_line_wrap_apply() {
+    let wrap = LineWrap {
+        max_chars: 5,
+        max_lines: 2,
+    };
+    assert_eq!(wrap.apply("ab\r\ncd"), vec!["ab\r\ncd"]);
+    assert_eq!(
+        wrap.apply("一二三四五六七八九十\nabc"),
+        vec!["一二三四五\r\n六七八九十", "abc"]
+    );
+    let wrap = LineWrap {
+        max_chars: 0,
+        max_lines: 1,
+    };
+    assert_eq!(wrap.apply("a\nb"), vec!["a", "b"]);
+}
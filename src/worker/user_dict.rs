@@ -0,0 +1,141 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::UserDict;
+
+/// First character of placeholders (Supplementary Private Use Area-A)
+const PLACEHOLDER_BASE: u32 = 0xF0000;
+const PLACEHOLDER_MAX: u32 = 0xFFFFD;
+
+/// User-defined entries, applied ahead of OpenCC dicts.
+///
+/// Matched terms are replaced with placeholders (private use characters)
+/// before conversion so that OpenCC dicts won't touch them, and then
+/// restored with the user-defined text.
+#[derive(Debug, Default)]
+pub(crate) struct UserConverter {
+    /// Term => index of `values`
+    entries: HashMap<String, usize>,
+    values: Vec<String>,
+    /// Length of the longest term in chars
+    max_len: usize,
+}
+
+impl UserConverter {
+    pub(crate) fn new(dict: &UserDict) -> Self {
+        let mut conv = Self::default();
+        let lines = |text: &str| {
+            text.lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+        for line in lines(&dict.entries) {
+            if let Some((term, values)) = line.split_once('\t')
+                && let Some(value) = values.split_whitespace().next()
+            {
+                conv.insert(term.trim(), value);
+            }
+        }
+        // glossary take precedence over entries
+        for term in lines(&dict.glossary) {
+            conv.insert(&term, &term);
+        }
+        conv
+    }
+
+    fn insert(&mut self, term: &str, value: &str) {
+        if term.is_empty() || self.values.len() as u32 > PLACEHOLDER_MAX - PLACEHOLDER_BASE {
+            return;
+        }
+        self.max_len = self.max_len.max(term.chars().count());
+        match self.entries.get(term) {
+            Some(&idx) => self.values[idx] = value.to_string(),
+            None => {
+                self.entries.insert(term.to_string(), self.values.len());
+                self.values.push(value.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Replace terms with placeholders, longest match first
+    pub(crate) fn mask<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        if self.is_empty() {
+            return text;
+        }
+        let offsets: Vec<_> = text
+            .char_indices()
+            .map(|(n, _)| n)
+            .chain([text.len()])
+            .collect();
+        let mut output = String::with_capacity(text.len());
+        let mut matched = false;
+        let mut i = 0;
+        while i + 1 < offsets.len() {
+            let found = (1..=self.max_len.min(offsets.len() - 1 - i))
+                .rev()
+                .find_map(|n| {
+                    let term = &text[offsets[i]..offsets[i + n]];
+                    self.entries.get(term).map(|&idx| (n, idx))
+                });
+            match found {
+                Some((n, idx)) => {
+                    output.push(char::from_u32(PLACEHOLDER_BASE + idx as u32).unwrap());
+                    matched = true;
+                    i += n;
+                }
+                None => {
+                    output.push_str(&text[offsets[i]..offsets[i + 1]]);
+                    i += 1;
+                }
+            }
+        }
+        if matched { output.into() } else { text }
+    }
+
+    /// Restore placeholders with user-defined text
+    pub(crate) fn unmask<'a>(&self, text: Cow<'a, str>) -> Cow<'a, str> {
+        let value = |c: char| {
+            (c as u32)
+                .checked_sub(PLACEHOLDER_BASE)
+                .and_then(|idx| self.values.get(idx as usize))
+        };
+        if self.is_empty() || !text.chars().any(|c| value(c).is_some()) {
+            return text;
+        }
+        let mut output = String::with_capacity(text.len());
+        for c in text.chars() {
+            match value(c) {
+                Some(value) => output.push_str(value),
+                None => output.push(c),
+            }
+        }
+        output.into()
+    }
+}
+
+#[test]
+fn test_user_converter() {
+    let conv = UserConverter::new(&UserDict {
+        entries: "# comment\n乾隆\t乾隆\n软件\t軟體 软体\ninvalid line\n".to_string(),
+        glossary: "小乾\n\n软件\n".to_string(),
+    });
+    let convert = |text: &'static str| {
+        let masked = conv.mask(text.into());
+        // pretend to be an OpenCC dict
+        let converted: String = masked
+            .chars()
+            .map(|c| if c == '乾' { '干' } else { c })
+            .collect();
+        conv.unmask(converted.into()).into_owned()
+    };
+    assert_eq!(convert("乾隆和小乾吃乾饭"), "乾隆和小乾吃干饭");
+    // glossary overrides entries
+    assert_eq!(convert("软件"), "软件");
+    assert!(matches!(conv.mask("无关".into()), Cow::Borrowed(_)));
+    assert!(UserConverter::new(&UserDict::default()).is_empty());
+}
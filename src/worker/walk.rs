@@ -1,7 +1,7 @@
 use std::{
     ffi::OsStr,
     io::{Cursor, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use js_sys::Uint8Array;
@@ -10,7 +10,7 @@ use zip::{ZipArchive, result::ZipError};
 
 use crate::FileWrap;
 
-use super::{ConvertError, FILE_SIZE_LIMIT};
+use super::{ConvertError, FILE_SIZE_LIMIT, name_tag::NameTag};
 
 pub(crate) trait ReadToVec {
     fn read_to_vec(&self, file: &File) -> Result<Vec<u8>, ConvertError>;
@@ -25,6 +25,13 @@ impl ReadToVec for FileReaderSync {
     }
 }
 
+fn is_ass_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(OsStr::to_str),
+        Some("ass") | Some("ssa")
+    )
+}
+
 #[derive(Debug)]
 struct ZipIterator {
     zip: ZipArchive<Cursor<Vec<u8>>>,
@@ -38,10 +45,18 @@ impl ZipIterator {
             file_idx: 0,
         })
     }
+
+    /// Number of ASS files in the zip
+    fn ass_files(&self) -> usize {
+        self.zip
+            .file_names()
+            .filter(|name| is_ass_file(Path::new(name)))
+            .count()
+    }
 }
 
 impl Iterator for ZipIterator {
-    type Item = Result<(PathBuf, Vec<u8>), ConvertError>;
+    type Item = Result<(PathBuf, Vec<u8>), WalkError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         while self.file_idx < self.zip.len() {
@@ -49,57 +64,110 @@ impl Iterator for ZipIterator {
             self.file_idx += 1;
             let mut file = match file {
                 Ok(file) => file,
-                Err(err) => return Some(Err(err.into())),
+                Err(err) => {
+                    let path = format!("#{}", self.file_idx - 1).into();
+                    return Some(Err((path, err.into())));
+                }
             };
             let path = match file.enclosed_name() {
                 Some(path) => path,
                 None => continue,
             };
-            match path.extension().and_then(OsStr::to_str) {
-                Some("ass") | Some("ssa") => (),
-                _ => {
-                    log::info!("skip file {:?}", path);
-                    continue;
-                }
+            if !is_ass_file(&path) {
+                log::info!("skip file {:?}", path);
+                continue;
             }
             let size = file.size().try_into().unwrap_or(usize::MAX);
             if size > FILE_SIZE_LIMIT {
-                return Some(Err(ConvertError::TooLarge {
+                let err = ConvertError::TooLarge {
                     size,
                     limit: FILE_SIZE_LIMIT,
-                }));
+                };
+                return Some(Err((path, err)));
             }
             let mut buf = Vec::new();
             return match file.read_to_end(&mut buf) {
                 Ok(_) => Some(Ok((path, buf))),
-                Err(err) => Some(Err(ZipError::Io(err).into())),
+                Err(err) => Some(Err((path, ZipError::Io(err).into()))),
             };
         }
         None
     }
 }
 
+/// Walked file, with its filename tag if parsing is enabled
+pub(crate) type WalkItem = (PathBuf, Vec<u8>, Option<NameTag>);
+/// Failed file & the error, the walk can go on after it
+pub(crate) type WalkError = (PathBuf, ConvertError);
+
 #[derive(Debug)]
 pub(crate) struct FileWalk {
     reader: FileReaderSync,
     files: Vec<FileWrap>,
     file_idx: usize,
     zip: Option<ZipIterator>,
+    name_tags: bool,
+    discovered: usize,
 }
 
 impl FileWalk {
-    pub(crate) fn new(files: Vec<FileWrap>, reader: FileReaderSync) -> Self {
+    pub(crate) fn new(files: Vec<FileWrap>, reader: FileReaderSync, name_tags: bool) -> Self {
+        let discovered = files
+            .iter()
+            .filter(|f| is_ass_file(Path::new(&f.0.name().to_ascii_lowercase())))
+            .count();
         Self {
             reader,
             files,
             file_idx: 0,
             zip: None,
+            name_tags,
+            discovered,
+        }
+    }
+
+    /// Number of ASS files found so far, including those in opened zips
+    pub(crate) fn discovered(&self) -> usize {
+        self.discovered
+    }
+
+    /// Prefix path inside zip with the zip name if there are multiple files
+    fn zip_path(&self, path: PathBuf) -> PathBuf {
+        if self.files.len() > 1 {
+            // TODO: move base path creation out of the loop
+            let mut base: PathBuf = self.files[self.file_idx].0.name().into();
+            base.set_extension("");
+            base.push(path);
+            base
+        } else {
+            path
+        }
+    }
+
+    /// Read top-level file, oversized one fails without being read
+    fn read_file(&self, file: &File) -> Result<Vec<u8>, ConvertError> {
+        let size = file.size() as usize;
+        if size > FILE_SIZE_LIMIT {
+            return Err(ConvertError::TooLarge {
+                size,
+                limit: FILE_SIZE_LIMIT,
+            });
         }
+        self.reader.read_to_vec(file)
+    }
+
+    fn item(&self, path: PathBuf, buf: Vec<u8>) -> WalkItem {
+        let tag = if self.name_tags {
+            NameTag::parse(&path.to_string_lossy())
+        } else {
+            None
+        };
+        (path, buf, tag)
     }
 }
 
 impl Iterator for FileWalk {
-    type Item = Result<(PathBuf, Vec<u8>), ConvertError>;
+    type Item = Result<WalkItem, WalkError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         while self.file_idx < self.files.len() {
@@ -108,18 +176,10 @@ impl Iterator for FileWalk {
                     // walk inside the zip file
                     match zip.next() {
                         Some(Ok((path, file))) => {
-                            let path = if self.files.len() > 1 {
-                                // TODO: move base path creation out of the loop
-                                let mut base: PathBuf = self.files[self.file_idx].0.name().into();
-                                base.set_extension("");
-                                base.push(path);
-                                base
-                            } else {
-                                path
-                            };
-                            return Some(Ok((path, file)));
+                            let path = self.zip_path(path);
+                            return Some(Ok(self.item(path, file)));
                         }
-                        Some(Err(err)) => return Some(Err(err)),
+                        Some(Err((path, err))) => return Some(Err((self.zip_path(path), err))),
                         None => {
                             // end of zip, read next file
                             self.zip = None;
@@ -137,19 +197,26 @@ impl Iterator for FileWalk {
                         .as_deref()
                     {
                         Some("zip") => {
-                            self.zip =
-                                match self.reader.read_to_vec(&file.0).and_then(ZipIterator::new) {
-                                    Ok(zip) => Some(zip),
-                                    Err(err) => return Some(Err(err)),
-                                };
+                            self.zip = match self.read_file(&file.0).and_then(ZipIterator::new) {
+                                Ok(zip) => {
+                                    self.discovered += zip.ass_files();
+                                    Some(zip)
+                                }
+                                Err(err) => {
+                                    // skip the broken zip, count it as a failed file
+                                    self.file_idx += 1;
+                                    self.discovered += 1;
+                                    return Some(Err((name, err)));
+                                }
+                            };
+                        }
+                        Some("ass") | Some("ssa") => {
+                            self.file_idx += 1;
+                            return match self.read_file(&file.0) {
+                                Ok(buf) => Some(Ok(self.item(name, buf))),
+                                Err(err) => Some(Err((name, err))),
+                            };
                         }
-                        Some("ass") | Some("ssa") => match self.reader.read_to_vec(&file.0) {
-                            Ok(buf) => {
-                                self.file_idx += 1;
-                                return Some(Ok((name, buf)));
-                            }
-                            Err(err) => return Some(Err(err)),
-                        },
                         _ => {
                             log::info!("skip file {:?}", name);
                             self.file_idx += 1;